@@ -0,0 +1,111 @@
+use crate::error::Error;
+use crate::parse::bounds::FunctionBounds;
+use crate::parse::graph::{determine_edges, split_to_basic_blocks, Graph};
+use crate::parse::{reassemble, Binary};
+
+//control flow graph of a function: basic blocks and the typed edges between them
+pub type Cfg = Graph;
+
+//a function of the binary together with its cfg
+pub struct Function {
+    //address the cfg was built from, the entry block starts here
+    pub address: u64,
+    pub name: String,
+    //range the instructions were decoded from
+    pub bounds: FunctionBounds,
+    //(instruction address, target address) of reachable code outside the bounds
+    pub escapes: Vec<(u64, u64)>,
+    pub cfg: Cfg,
+}
+
+//where the cfg starts, names are resolved when the cfg is built
+enum Start {
+    Address(u64),
+    Symbol(String),
+}
+
+//builds the cfg of one function of a binary
+//
+//    let binary = Binary::load(Path::new("a.out"))?;
+//    let function = CfgBuilder::new(&binary).symbol("main").build()?;
+pub struct CfgBuilder<'a> {
+    binary: &'a Binary,
+    start: Start,
+    bounds: Option<FunctionBounds>,
+}
+
+impl<'a> CfgBuilder<'a> {
+    //starts at the entry point of the binary unless address or symbol is called
+    pub fn new(binary: &'a Binary) -> Self {
+        CfgBuilder {
+            binary,
+            start: Start::Address(binary.entry),
+            bounds: None,
+        }
+    }
+
+    pub fn address(mut self, address: u64) -> Self {
+        self.start = Start::Address(address);
+        self
+    }
+
+    //a hex address with 0x prefix or a (partial) function name
+    pub fn symbol(mut self, name: &str) -> Self {
+        self.start = Start::Symbol(name.to_string());
+        self
+    }
+
+    //use these bounds instead of looking them up in the symbols and .eh_frame
+    pub fn bounds(mut self, bounds: FunctionBounds) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn build(&self) -> Result<Function, Error> {
+        let address = match &self.start {
+            Start::Address(address) => *address,
+            Start::Symbol(name) => self.binary.resolve_address(name)?,
+        };
+
+        let offset = self
+            .binary
+            .virtual_address_to_offset(address)
+            .ok_or(Error::AddressUnmapped(address))?;
+
+        let bounds = self
+            .bounds
+            .unwrap_or_else(|| self.binary.function_bounds(address));
+
+        let reassembly = reassemble(
+            self.binary.bitness,
+            &self.binary.bytes,
+            &offset,
+            &address,
+            &bounds,
+            &self.binary.no_return,
+        );
+
+        //the start address itself didnt decode
+        if !reassembly
+            .assembly_line
+            .iter()
+            .any(|(instruction_address, _)| *instruction_address == address)
+        {
+            return Err(Error::Decode(address));
+        }
+
+        let mut cfg = Graph::new();
+
+        cfg = split_to_basic_blocks(reassembly.assembly_line, cfg, &self.binary.no_return);
+
+        cfg = determine_edges(cfg, &self.binary.no_return);
+
+        Ok(Function {
+            address,
+            name: self.binary.function_name(address),
+            bounds,
+            escapes: reassembly.escapes,
+            cfg,
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+//max number of candidates listed in an ambiguous symbol or archive member error
+const MAX_LISTED_CANDIDATES: usize = 20;
+
+//every error the library can return
+#[derive(Debug)]
+pub enum Error {
+    //reading or writing a file failed
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    //the file starts with neither the ELF magic, the MZ of a PE file nor a Mach-O magic
+    UnknownFormat,
+    //EI_CLASS of the ELF header is neither ELFCLASS32 nor ELFCLASS64, the value is the class byte
+    WrongClass(u8),
+    //e_machine is not EM_386 or EM_X86_64, the COFF machine not i386 or AMD64 or the Mach-O
+    //cputype not x86 or x86_64
+    UnsupportedMachine(u32),
+    //the headers or tables of the file couldnt be parsed
+    Malformed(String),
+    //.eh_frame or .eh_frame_hdr couldnt be parsed
+    EhFrame(String),
+    //the address is not backed by bytes of a loadable segment
+    AddressUnmapped(u64),
+    //the input looked like a hex address but isnt one
+    InvalidAddress(String),
+    //no function symbol matches the name
+    SymbolNotFound(String),
+    //more than one function matches the name, candidates are (address, name)
+    AmbiguousSymbol {
+        name: String,
+        candidates: Vec<(u64, String)>,
+    },
+    //no valid instruction could be decoded at the address
+    Decode(u64),
+    //raw code can only be decoded as 16, 32 or 64-bit code
+    InvalidBitness(u32),
+    //the ar archive has no member with the name, or no name was given and it has more than one
+    ArchiveMember {
+        name: Option<String>,
+        members: Vec<String>,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::UnknownFormat => write!(f, "Not an ELF, PE or Mach-O file"),
+            Error::WrongClass(class) => write!(f, "Unknown ELF class {}", class),
+            Error::UnsupportedMachine(machine) => {
+                write!(f, "Not an x86 binary (machine {:#X})", machine)
+            }
+            Error::Malformed(message) => write!(f, "Malformed binary: {}", message),
+            Error::EhFrame(message) => write!(f, "Malformed .eh_frame: {}", message),
+            Error::AddressUnmapped(address) => {
+                write!(f, "Address {:#X} is not mapped in the binary", address)
+            }
+            Error::InvalidAddress(input) => write!(f, "Invalid address {}", input),
+            Error::SymbolNotFound(name) => write!(f, "No function symbol matches {}", name),
+            Error::AmbiguousSymbol { name, candidates } => {
+                write!(
+                    f,
+                    "Symbol name {} is ambiguous, {} candidates:",
+                    name,
+                    candidates.len()
+                )?;
+                for (address, candidate) in candidates.iter().take(MAX_LISTED_CANDIDATES) {
+                    write!(f, "\n  0x{:X} {}", address, candidate)?;
+                }
+                if candidates.len() > MAX_LISTED_CANDIDATES {
+                    write!(
+                        f,
+                        "\n  ... and {} more",
+                        candidates.len() - MAX_LISTED_CANDIDATES
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Decode(address) => write!(f, "No valid instruction at {:#X}", address),
+            Error::InvalidBitness(bitness) => {
+                write!(f, "Bitness {} is not 16, 32 or 64", bitness)
+            }
+            Error::ArchiveMember { name, members } => {
+                match name {
+                    Some(name) => write!(f, "The archive has no member {}, members:", name)?,
+                    None => write!(f, "The archive has {} members, select one:", members.len())?,
+                }
+                for member in members.iter().take(MAX_LISTED_CANDIDATES) {
+                    write!(f, "\n  {}", member)?;
+                }
+                if members.len() > MAX_LISTED_CANDIDATES {
+                    write!(
+                        f,
+                        "\n  ... and {} more",
+                        members.len() - MAX_LISTED_CANDIDATES
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<goblin::error::Error> for Error {
+    fn from(err: goblin::error::Error) -> Self {
+        Error::Malformed(err.to_string())
+    }
+}
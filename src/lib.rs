@@ -0,0 +1,25 @@
+//! Control flow graph generator for x86 ELF, PE and Mach-O binaries, 32 and 64-bit
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use cfg_generator::{Binary, CfgBuilder};
+//!
+//! let binary = Binary::load(Path::new("a.out"))?;
+//! let function = CfgBuilder::new(&binary).symbol("main").build()?;
+//! for block in &function.cfg.nodes {
+//!     println!("{:#X}..={:#X}", block.start_addr, block.end_addr);
+//! }
+//! # Ok::<(), cfg_generator::Error>(())
+//! ```
+
+pub mod cfg;
+pub mod error;
+pub mod parse;
+pub mod syntax;
+
+pub use cfg::{Cfg, CfgBuilder, Function};
+pub use error::Error;
+pub use parse::bounds::{BoundsSource, FunctionBounds};
+pub use parse::graph::{BasicBlock, Edge, EdgeKind};
+pub use parse::{Binary, Format};
+pub use syntax::{AsmFormatter, FormatOptions, Syntax};
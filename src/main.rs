@@ -1,18 +1,485 @@
-use std::env;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 
-mod parse;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+
+use cfg_generator::parse::demangle::NameOptions;
+use cfg_generator::parse::export::ascii::Charset;
+use cfg_generator::parse::export::dot::{create_dot, DotConfig, DotLabels};
+use cfg_generator::parse::graph::log_graph;
+use cfg_generator::parse::{callgraph, export, listing, whole_binary};
+use cfg_generator::{AsmFormatter, Binary, CfgBuilder, Error, FormatOptions, Function, Syntax};
+
+/// Control flow graph generator for x86 ELF, PE and Mach-O binaries, 32 and 64-bit
+///
+/// The requested output goes to stdout unless -o is given, diagnostics go to stderr
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// More diagnostics: -v info, -vv debug, -vvv trace
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Less diagnostics: -q errors only, -qq nothing
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    quiet: u8,
+    /// Show symbol names mangled, the way they are in the symbol tables
+    #[arg(long, global = true)]
+    raw_names: bool,
+    /// Drop the hashes of demangled rust names
+    #[arg(long, global = true)]
+    strip_hashes: bool,
+    /// Hex address a PIE, shared object, DLL, dylib or --raw file was loaded at, addresses are
+    /// runtime ones then
+    #[arg(long, global = true, value_parser = parse_address)]
+    base: Option<u64>,
+    /// Read the file as headerless code of this bitness (16, 32 or 64) like shellcode, a memory
+    /// dump or a boot sector, --base is the address it is loaded at
+    #[arg(long, global = true, value_name = "BITS", value_parser = parse_bitness)]
+    raw: Option<u32>,
+    /// Object file to read from an ar archive (.a), not needed if it has only one
+    #[arg(long, global = true, value_name = "NAME")]
+    member: Option<String>,
+    /// Hex offset of the entry point in a --raw file
+    #[arg(long, global = true, value_name = "OFFSET", value_parser = parse_address, requires = "raw")]
+    entry: Option<u64>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Control flow graph of one function
+    Cfg {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        /// Hex address with 0x prefix or a (partial) function name
+        function: String,
+        #[command(flatten)]
+        output: Output,
+        /// Format of the output
+        #[arg(short, long, value_enum, default_value_t = CfgFormat::Dot)]
+        format: CfgFormat,
+        #[command(flatten)]
+        instructions: Instructions,
+        /// How the instructions are laid out in the dot nodes
+        #[arg(long, value_enum, default_value_t = DotLabelsArg::Text)]
+        dot_labels: DotLabelsArg,
+        /// Extra dot graph attribute like rankdir=LR, can be repeated
+        #[arg(long, value_name = "NAME=VALUE", value_parser = parse_attribute)]
+        graph_attr: Vec<(String, String)>,
+    },
+    /// Call graph of the functions reachable from a root function
+    Callgraph {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        /// Hex address with 0x prefix or a (partial) function name
+        root: String,
+        /// How many calls deep the functions are followed
+        #[arg(short = 'd', long)]
+        max_depth: Option<u32>,
+        #[command(flatten)]
+        output: Output,
+        #[command(flatten)]
+        instructions: Instructions,
+        /// Format of the output
+        #[arg(short, long, value_enum, default_value_t = CallGraphFormat::Dot)]
+        format: CallGraphFormat,
+    },
+    /// Functions from the .eh_frame FDEs, .pdata or LC_FUNCTION_STARTS, works on stripped binaries
+    Functions {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        #[command(flatten)]
+        output: Output,
+        /// Format of the output
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Entry point, segments, sections and symbol counts of the binary
+    Info {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        #[command(flatten)]
+        output: Output,
+        /// Format of the output
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Instructions of one function grouped by basic block
+    Disasm {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        /// Hex address with 0x prefix or a (partial) function name
+        function: String,
+        #[command(flatten)]
+        output: Output,
+        #[command(flatten)]
+        instructions: Instructions,
+    },
+    /// CFG of every function as one dot file each, plus an index and the whole call graph
+    All {
+        /// Path of the ELF, PE or Mach-O binary
+        binary: PathBuf,
+        /// Directory the files are written to, it is created if it doesnt exist
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        instructions: Instructions,
+    },
+}
+
+#[derive(clap::Args)]
+struct Output {
+    /// File the output is written to, - for stdout
+    #[arg(short, long, default_value = "-")]
+    output: String,
+}
+
+//how the instructions are printed
+#[derive(clap::Args)]
+struct Instructions {
+    /// Assembler syntax of the instructions
+    #[arg(short, long, value_enum, default_value_t = SyntaxArg::Nasm)]
+    syntax: SyntaxArg,
+    /// Print hex numbers in lowercase
+    #[arg(long)]
+    lowercase_hex: bool,
+    /// Print branch targets and rip relative memory operands as plain addresses instead of
+    /// symbol+offset
+    #[arg(long)]
+    no_symbols: bool,
+    /// Print the bytes of every instruction
+    #[arg(long)]
+    raw_bytes: bool,
+}
+
+impl Instructions {
+    fn options(&self) -> FormatOptions {
+        FormatOptions {
+            syntax: self.syntax.into(),
+            uppercase_hex: !self.lowercase_hex,
+            symbolize: !self.no_symbols,
+            raw_bytes: self.raw_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CfgFormat {
+    Dot,
+    /// Blocks, instructions and edges, see src/parse/export/json.rs for the schema
+    Json,
+    /// GraphML for yEd and NetworkX
+    Graphml,
+    /// GEXF for Gephi
+    Gexf,
+    /// Interactive page that works offline
+    Html,
+    /// Mermaid flowchart for markdown
+    Mermaid,
+    /// Boxes and lines drawn with plain ascii for the terminal
+    Ascii,
+    /// Boxes and lines drawn with box drawing characters for the terminal
+    Unicode,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CallGraphFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DotLabelsArg {
+    /// One left justified line per instruction
+    Text,
+    /// Table with address, bytes, mnemonic and operands columns
+    Table,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SyntaxArg {
+    Nasm,
+    Intel,
+    Masm,
+    /// AT&T syntax
+    Gas,
+    /// Masm like, faster on big binaries
+    Fast,
+}
+
+impl From<SyntaxArg> for Syntax {
+    fn from(syntax: SyntaxArg) -> Self {
+        match syntax {
+            SyntaxArg::Nasm => Syntax::Nasm,
+            SyntaxArg::Intel => Syntax::Intel,
+            SyntaxArg::Masm => Syntax::Masm,
+            SyntaxArg::Gas => Syntax::Gas,
+            SyntaxArg::Fast => Syntax::Fast,
+        }
+    }
+}
+
+//hex address with or without 0x prefix
+fn parse_address(input: &str) -> Result<u64, String> {
+    let hex = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+    u64::from_str_radix(hex, 16).map_err(|_| format!("expected a hex address, got {}", input))
+}
+
+//16, 32 or 64
+fn parse_bitness(input: &str) -> Result<u32, String> {
+    match input {
+        "16" => Ok(16),
+        "32" => Ok(32),
+        "64" => Ok(64),
+        _ => Err(format!("expected 16, 32 or 64, got {}", input)),
+    }
+}
+
+//NAME=VALUE of a dot attribute
+fn parse_attribute(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got {}", input)),
+    }
+}
 
 fn main() {
-    //vector for cmdl args
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse();
+
+    init_logging(i32::from(cli.verbose) - i32::from(cli.quiet));
+
+    let load = Load {
+        names: NameOptions {
+            demangle: !cli.raw_names,
+            strip_hashes: cli.strip_hashes,
+        },
+        base: cli.base,
+        raw: cli.raw,
+        entry: cli.entry,
+        member: cli.member,
+    };
 
-    //if not exactly 3 cmdl args error and exit return code
-    if args.len() != 3 {
-        eprintln!("Usage: ./cfg_generator <binary_path> <memory_address>");
+    if let Err(err) = run(cli.command, &load) {
+        eprintln!("Error: {}", err);
         std::process::exit(1);
     }
-    let binary_path = &args[1];
-    let virtual_address = u64::from_str_radix(&args[2].strip_prefix("0x").unwrap(), 16).unwrap();
+}
+
+//the global options every command loads its binary with
+struct Load {
+    names: NameOptions,
+    base: Option<u64>,
+    //bitness of a raw file
+    raw: Option<u32>,
+    entry: Option<u64>,
+    //object file of an archive
+    member: Option<String>,
+}
+
+impl Load {
+    fn binary(&self, path: &Path) -> Result<Binary, Error> {
+        //raw code is loaded at --base right away, there is nothing to rebase
+        if let Some(bitness) = self.raw {
+            let mut binary = Binary::load_raw(
+                path,
+                bitness,
+                self.base.unwrap_or(0),
+                self.entry.unwrap_or(0),
+            )?;
+            binary.names = self.names;
+            return Ok(binary);
+        }
+
+        let mut binary = Binary::load_member(path, self.member.as_deref())?;
+        binary.names = self.names;
+
+        match (self.base, binary.position_independent) {
+            (Some(base), true) => binary.rebase(base),
+            (Some(_), false) => {
+                log::warn!("--base ignored, the binary is loaded at its own addresses")
+            }
+            (None, true) => log::info!(
+                "position independent binary, addresses are relative to its load address, \
+                 use --base for runtime addresses"
+            ),
+            (None, false) => {}
+        }
+
+        Ok(binary)
+    }
+}
+
+fn run(command: Command, load: &Load) -> Result<(), Error> {
+    match command {
+        Command::Cfg {
+            binary,
+            function,
+            output,
+            format,
+            instructions,
+            dot_labels,
+            graph_attr,
+        } => {
+            let binary = load.binary(&binary)?;
+            let function = CfgBuilder::new(&binary).symbol(&function).build()?;
+            let mut formatter = AsmFormatter::new(&instructions.options(), &binary);
+            warn_escapes(&function);
+            log_graph(&function.cfg, &mut formatter);
+
+            let mut dot_config = DotConfig {
+                graph_name: function.name.clone(),
+                labels: match dot_labels {
+                    DotLabelsArg::Text => DotLabels::Text,
+                    DotLabelsArg::Table => DotLabels::Table,
+                },
+                ..DotConfig::default()
+            };
+            dot_config.graph_attributes.extend(graph_attr);
+
+            let content = match format {
+                CfgFormat::Dot => create_dot(&function.cfg, &mut formatter, &dot_config),
+                CfgFormat::Json => export::json::create_cfg_json(&function, &mut formatter),
+                CfgFormat::Graphml => {
+                    export::graphml::create_graphml(&function.cfg, &mut formatter)
+                }
+                CfgFormat::Gexf => export::gexf::create_gexf(&function.cfg, &mut formatter),
+                CfgFormat::Html => export::html::create_html(&function, &mut formatter),
+                CfgFormat::Mermaid => export::mermaid::create_mermaid(&function, &mut formatter),
+                CfgFormat::Ascii => {
+                    export::ascii::create_ascii(&function, &mut formatter, Charset::Ascii)
+                }
+                CfgFormat::Unicode => {
+                    export::ascii::create_ascii(&function, &mut formatter, Charset::Unicode)
+                }
+            };
+            write_output(&output.output, &content)
+        }
+        Command::Callgraph {
+            binary,
+            root,
+            max_depth,
+            output,
+            instructions,
+            format,
+        } => {
+            let binary = load.binary(&binary)?;
+            let root = binary.resolve_address(&root)?;
+            let mut formatter = AsmFormatter::new(&instructions.options(), &binary);
+            let call_graph = callgraph::build_call_graph(&binary, root, max_depth, &mut formatter)?;
+
+            let content = match format {
+                CallGraphFormat::Dot => callgraph::create_call_graph_dot(&call_graph),
+                CallGraphFormat::Json => callgraph::create_call_graph_json(&call_graph),
+            };
+            write_output(&output.output, &content)
+        }
+        Command::Functions {
+            binary,
+            output,
+            format,
+        } => {
+            let binary = load.binary(&binary)?;
+
+            let content = match format {
+                ListFormat::Text => listing::create_function_list_text(&binary),
+                ListFormat::Json => listing::create_function_list_json(&binary),
+            };
+            write_output(&output.output, &content)
+        }
+        Command::Info {
+            binary,
+            output,
+            format,
+        } => {
+            let binary = load.binary(&binary)?;
+
+            let content = match format {
+                ListFormat::Text => listing::create_info_text(&binary),
+                ListFormat::Json => listing::create_info_json(&binary),
+            };
+            write_output(&output.output, &content)
+        }
+        Command::Disasm {
+            binary,
+            function,
+            output,
+            instructions,
+        } => {
+            let binary = load.binary(&binary)?;
+            let function = CfgBuilder::new(&binary).symbol(&function).build()?;
+            let mut formatter = AsmFormatter::new(&instructions.options(), &binary);
+            warn_escapes(&function);
+
+            let content = listing::create_disassembly(&function, &mut formatter);
+            write_output(&output.output, &content)
+        }
+        Command::All {
+            binary,
+            output,
+            instructions,
+        } => {
+            let binary = load.binary(&binary)?;
+            let mut formatter = AsmFormatter::new(&instructions.options(), &binary);
+            whole_binary::generate_all_cfgs(&binary, &output, &mut formatter)
+        }
+    }
+}
+
+fn warn_escapes(function: &Function) {
+    for (address, target) in &function.escapes {
+        log::warn!(
+            "code at {:#X} reaches {:#X} outside of the function bounds {:#X}..{:#X} ({:?})",
+            address,
+            target,
+            function.bounds.start,
+            function.bounds.end,
+            function.bounds.source
+        );
+    }
+}
+
+//write the content to the file at path, - is stdout
+fn write_output(path: &str, content: &str) -> Result<(), Error> {
+    if path == "-" {
+        return match std::io::stdout().lock().write_all(content.as_bytes()) {
+            //the reader of the pipe (like head) has seen enough
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+            result => result.map_err(|err| Error::io("<stdout>", err)),
+        };
+    }
+
+    std::fs::write(path, content).map_err(|err| Error::io(path, err))?;
+    log::info!("Wrote {}", Path::new(path).display());
+
+    Ok(())
+}
+
+//diagnostics go to stderr so stdout only has the requested output
+//warnings are shown by default, RUST_LOG overrides the flags
+fn init_logging(verbosity: i32) {
+    let level = match verbosity {
+        i32::MIN..=-2 => LevelFilter::Off,
+        -1 => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
 
-    parse::generate_cfg(binary_path, &virtual_address);
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
 }
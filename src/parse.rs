@@ -1,38 +1,101 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::path::Path;
 
-use goblin::elf::Elf;
 use iced_x86::{Decoder, DecoderOptions, FlowControl, Instruction};
 
-use petgraph::dot::{Config, Dot};
-use petgraph::stable_graph::NodeIndex;
-use petgraph::Graph;
+use crate::error::Error;
 
-use self::graph::print_graph;
+pub mod bounds;
+pub mod callgraph;
+pub mod demangle;
+pub mod eh_frame;
+pub mod elf;
+pub mod export;
+pub mod graph;
+pub mod listing;
+pub mod macho;
+pub mod object;
+pub mod pe;
+pub mod symbols;
+pub mod whole_binary;
 
-mod graph;
+//file formats a binary can be loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Elf,
+    //windows executables and DLLs
+    Pe,
+    //macOS executables and dylibs, thin or fat
+    MachO,
+    //code without a container like shellcode, a memory dump or a boot sector, see Binary::raw
+    Raw,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Elf => "ELF",
+            Format::Pe => "PE",
+            Format::MachO => "Mach-O",
+            Format::Raw => "raw",
+        }
+    }
+}
 
-pub fn check_if_valid_elf_64(path: &Path) -> Result<(), String> {
+pub fn check_if_valid_binary(path: &Path) -> Result<(), Error> {
     let mut file = match File::open(path) {
         Ok(file) => file,
-        Err(err) => return Err(err.to_string()),
+        Err(err) => return Err(Error::io(path, err)),
     };
 
-    //read only the necessary bytes to check the ELF header
-    //16 bytes to identify if its an elf
+    //read only the necessary bytes to check the header
+    //16 bytes to identify if its an elf, a PE starts with MZ and a Mach-O with a 4 byte magic
     let mut header = [0; 16];
     //if the result of the iflet is error execute code, if not an error go on
     if let Err(err) = file.read_exact(&mut header) {
-        return Err(err.to_string());
+        //a file shorter than the identification cant be a binary
+        if err.kind() == ErrorKind::UnexpectedEof {
+            return Err(Error::UnknownFormat);
+        }
+        return Err(Error::io(path, err));
+    }
+
+    //the members of an ar archive are checked once they are extracted
+    if header.starts_with(goblin::archive::MAGIC) {
+        return Ok(());
     }
+
+    identify_format(&header).map(|_| ())
+}
+
+//format of the file from its first bytes, the rest of the PE and Mach-O headers is checked by
+//goblin later
+pub fn identify_format(header: &[u8]) -> Result<Format, Error> {
+    if header.starts_with(b"MZ") {
+        return Ok(Format::Pe);
+    }
+    //little endian 32 and 64-bit Mach-O, the fat header is big endian
+    if header.starts_with(&[0xCE, 0xFA, 0xED, 0xFE])
+        || header.starts_with(&[0xCF, 0xFA, 0xED, 0xFE])
+        || header.starts_with(&[0xCA, 0xFE, 0xBA, 0xBE])
+    {
+        return Ok(Format::MachO);
+    }
+    check_elf_identification(header)?;
+    Ok(Format::Elf)
+}
+
+//check the 16 identification bytes at the start of the ELF header
+pub fn check_elf_identification(header: &[u8]) -> Result<(), Error> {
     //check if the file is an ELF file
-    if header[..4] != [0x7F, b'E', b'L', b'F'] {
-        return Err("Not an ELF file".to_string());
+    if header.len() < 16 || header[..4] != [0x7F, b'E', b'L', b'F'] {
+        return Err(Error::UnknownFormat);
     }
-    //check if its a 64-bit ELF file, 01 is 32
-    if header[4] != 2 {
-        return Err("Not 64-bit".to_string());
+    //01 is a 32-bit ELF file, 02 a 64-bit one
+    if header[4] != 1 && header[4] != 2 {
+        return Err(Error::WrongClass(header[4]));
     }
     //next byte represent endianess 01 little endian, 02 big endian
 
@@ -41,177 +104,1300 @@ pub fn check_if_valid_elf_64(path: &Path) -> Result<(), String> {
 
 //TODO: reading only the nescessary parts of the binary
 //load the whole binary into vec
-pub fn load_binary(path: &Path) -> Result<Vec<u8>, String> {
+pub fn load_binary(path: &Path) -> Result<Vec<u8>, Error> {
     //check if valid elf before loading it
 
     //handle file open error
     //could use iflet here as well
     let mut file = match File::open(path) {
         Ok(file) => file,
-        Err(err) => return Err(err.to_string()),
+        Err(err) => return Err(Error::io(path, err)),
     };
 
     let mut buffer = Vec::new();
 
-    //handle reading error, implicit return of Result<buffer(which is a vec),string (which is an error)>
+    //handle reading error, implicit return of Result<buffer(which is a vec),Error>
     match file.read_to_end(&mut buffer) {
         //using Ok(_) means match any value and ignore it, if the result is Ok regardless of the value within Ok return Ok(buffer)
         Ok(_) => Ok(buffer),
-        Err(err) => Err(err.to_string()),
+        Err(err) => Err(Error::io(path, err)),
     }
-
-    //print elf metadata
-    //println!("elf: {:#?}", &elf);
-}
-
-//print binary for debug purposes
-pub fn print_binary(buffer: &[u8], offset: u64) {
-    let usize_offset = offset as usize;
-    //TODO: check if offset is in bounds
-
-    for &byte in &buffer[usize_offset..] {
-        //{:02X} format specifier, each byte is printed 2 chars wide and in hex
-        print!("{:02X} ", byte);
-    }
-    println!();
 }
 
 //function to calculate the given virt_addrs location in the raw bytes
-pub fn calculate_offset(buffer: &[u8], address: &u64) -> u64 {
-    let parsed = Elf::parse(&buffer);
-
-    //print elf metadata
-    println!("elf: {:#?}", &parsed);
-
+pub fn calculate_offset(segments: &[Segment], address: &u64) -> Result<u64, Error> {
     //p_vaddr: starting virtual addr for the segment
     //p_offset: offset in the file where the segment begins
 
     //virtual_offset in segment determines where our address is in the segment = virtual addr - program_header.p_vaddr
     //file_offset the absolute offset from the start to the segment(where our address is in) + the offset from the segment start and our given addr = program_header.p_offset + virtual_offset
-    let mut virtual_offset: u64 = 0;
-    let mut file_offset: u64 = 0;
-
-    //iterate thorugh the program headers
-    //TODO:unwrap panics if parsed is error, handle parsed above
-    for i in &parsed.unwrap().program_headers {
-        //check if the given vaddr is between the start and end of the segment
-        let segment_end = &i.p_vaddr + &i.p_memsz;
-        if address >= &i.p_vaddr && address < &segment_end {
-            virtual_offset = address - i.p_vaddr;
-            file_offset = i.p_offset + virtual_offset;
-            break;
+
+    //iterate thorugh the segments
+    for segment in segments {
+        //check if the given vaddr is between the start and the end of the bytes of the segment
+        //the part of the segment past file_size (.bss) has no bytes in the file
+        if *address >= segment.virtual_address
+            && address - segment.virtual_address < segment.file_size
+        {
+            let virtual_offset = address - segment.virtual_address;
+            return Ok(segment.file_offset + virtual_offset);
         }
     }
-    file_offset
+
+    Err(Error::AddressUnmapped(*address))
 }
 
-pub fn calculate_segment_offset(buffer: &[u8], address: &u64) -> u64 {
-    let parsed = Elf::parse(&buffer);
-    //for i in &parsed.unwrap().program_headers {}
-    return 5;
+//result of reassemble
+pub struct Reassembly {
+    //all instructions of the function sorted by address, use split_to_basic_blocks on it
+    pub assembly_line: Vec<(u64, Instruction)>,
+    //(instruction address, target address) of reachable code outside the function bounds
+    //tail jumps are not escapes, conditional branches and fallthroughs out of the function are
+    pub escapes: Vec<(u64, u64)>,
 }
 
-//this function returns a vec with a tuple of address, and Instruction
-//the return contains all instructions in the function, use split_to_basic_blocks on it
+//this function decodes every instruction of the function containing virtual_address
+//recursive descent: every branch target reachable from the start address is followed
+//until each path ends in a return, tail jump, indirect branch or no-return call
+//if the bounds are authoritative the remaining gaps inside them are decoded as well
 pub fn reassemble(
+    bitness: u32,
     buffer: &[u8],
     file_offset: &u64,
     virtual_address: &u64,
-) -> Vec<(u64, Instruction)> {
-    let start_index = *file_offset as usize;
-    let mut decoder = Decoder::with_ip(
-        64,
-        &buffer[start_index..], //changed from [start_index..] to &buffer start
-        *virtual_address,
-        DecoderOptions::NONE,
-    );
+    bounds: &bounds::FunctionBounds,
+    no_return: &HashSet<u64>,
+) -> Reassembly {
+    //decoded instructions keyed by address, BTreeMap keeps them sorted for splitting
+    let mut decoded: BTreeMap<u64, Instruction> = BTreeMap::new();
+    let mut escapes = Vec::new();
+
+    //addresses that still have to be decoded
+    let mut worklist = vec![*virtual_address];
+    //gap addresses which didnt decode to anything, they are treated as a 1 byte hole
+    let mut undecodable = HashSet::new();
+    //every byte of the bounds before it is covered, gaps are only searched from here on
+    let mut gap_cursor = bounds.start;
 
     // Initialize this outside the loop because decode_out() writes to every field
     let mut instruction = Instruction::default();
 
-    let mut assembly_line = Vec::new();
+    loop {
+        while let Some(address) = worklist.pop() {
+            if decoded.contains_key(&address) {
+                continue;
+            }
+
+            //translate the address into the raw bytes, skip it if its outside the buffer
+            let start_index = match address_to_index(buffer, file_offset, virtual_address, address)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let mut decoder = Decoder::with_ip(
+                bitness,
+                &buffer[start_index..],
+                address,
+                DecoderOptions::NONE,
+            );
+
+            while decoder.can_decode() {
+                //fell through the end of the function
+                if !bounds.contains(decoder.ip()) {
+                    escapes.push((instruction.ip(), decoder.ip()));
+                    break;
+                }
 
-    while decoder.can_decode() {
-        decoder.decode_out(&mut instruction);
+                decoder.decode_out(&mut instruction);
 
-        //instruction.ip is the address where the instruction is
-        assembly_line.push((instruction.ip(), instruction));
+                //ran into garbage, this path cant be followed any further
+                if instruction.is_invalid() {
+                    break;
+                }
 
-        //break if instruction is Return type
-        //if check is at the end so ret is included in the output
-        if instruction.flow_control() == FlowControl::Return {
+                //the rest of this path was already decoded from another branch
+                if decoded.contains_key(&instruction.ip()) {
+                    break;
+                }
+
+                //instruction.ip is the address where the instruction is
+                decoded.insert(instruction.ip(), instruction);
+
+                let target = instruction.near_branch_target();
+                match instruction.flow_control() {
+                    //follow the taken arm later, keep decoding the not taken arm
+                    FlowControl::ConditionalBranch => {
+                        if bounds.contains(target) {
+                            worklist.push(target);
+                        } else {
+                            escapes.push((instruction.ip(), target));
+                        }
+                    }
+                    FlowControl::UnconditionalBranch => {
+                        //a jump out of the function is a tail jump and ends the path
+                        if bounds.contains(target) {
+                            worklist.push(target);
+                        }
+                        break;
+                    }
+                    FlowControl::Call | FlowControl::IndirectCall
+                        if graph::calls_no_return(&instruction, no_return) =>
+                    {
+                        break;
+                    }
+                    //IndirectBranch,Return,Exception end the path
+                    FlowControl::IndirectBranch | FlowControl::Return | FlowControl::Exception => {
+                        break;
+                    }
+                    //Next,Call,IndirectCall,Interrupt,XbeginXabortXend just continue
+                    _ => {}
+                }
+            }
+        }
+
+        //heuristic bounds are only a guess, so only reachable code is decoded
+        if !bounds.is_authoritative() {
             break;
         }
+
+        //code only reachable through jump tables or the unwinder (landing pads) is still part
+        //of the function, decode from the first byte that isnt covered yet
+        match find_gap(&decoded, &undecodable, gap_cursor, bounds.end) {
+            Some(gap) => {
+                worklist.push(gap);
+                //mark it now, if it decodes the decoded map covers it anyway
+                undecodable.insert(gap);
+                gap_cursor = gap;
+            }
+            _ => break,
+        }
     }
 
-    //print vector for dbg purposes
+    let assembly_line: Vec<(u64, Instruction)> = decoded.into_iter().collect();
+
     for (address, instruction) in &assembly_line {
-        println!("{:016X}, {}", instruction.ip(), instruction);
+        log::trace!("{:016X}, {}", address, instruction);
     }
 
-    assembly_line
+    Reassembly {
+        assembly_line,
+        escapes,
+    }
 }
 
-//game loop function that contains every other function, this is called in main
-pub fn generate_cfg(binary_path: &String, virtual_address: &u64) {
-    //TODO: dont unwrap, send error upwards
-    let _ = check_if_valid_elf_64(Path::new(binary_path)).unwrap();
+//index into the raw bytes of an address, virtual_address is located at file_offset
+fn address_to_index(
+    buffer: &[u8],
+    file_offset: &u64,
+    virtual_address: &u64,
+    address: u64,
+) -> Option<usize> {
+    let index = if address >= *virtual_address {
+        file_offset.checked_add(address - virtual_address)?
+    } else {
+        file_offset.checked_sub(virtual_address - address)?
+    };
 
-    let binary = load_binary(Path::new(binary_path)).unwrap();
+    if (index as usize) < buffer.len() {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
 
-    let offset = calculate_offset(&binary, &virtual_address);
+//first address in [cursor, end) which is neither covered by a decoded instruction nor a known
+//undecodable byte
+//decoding a gap only covers bytes that werent covered before, so the bytes in front of a gap
+//stay covered and the next search can start at it
+fn find_gap(
+    decoded: &BTreeMap<u64, Instruction>,
+    undecodable: &HashSet<u64>,
+    mut cursor: u64,
+    end: u64,
+) -> Option<u64> {
+    while cursor < end {
+        if undecodable.contains(&cursor) {
+            cursor += 1;
+            continue;
+        }
+        //the instruction starting at or before the cursor, if it overlaps the cursor skip past it
+        match decoded.range(..=cursor).next_back() {
+            Some((address, instruction)) if address + instruction.len() as u64 > cursor => {
+                cursor = address + instruction.len() as u64;
+            }
+            _ => return Some(cursor),
+        }
+    }
+
+    None
+}
 
-    let assembly_line = reassemble(&binary, &offset, &virtual_address);
+//copy of the member of an ar archive, member can be left out if there is only one
+fn extract_member(archive: &[u8], member: Option<&str>) -> Result<Vec<u8>, Error> {
+    let parsed = goblin::archive::Archive::parse(archive)?;
+    let members = parsed.members();
 
-    let mut graph = graph::Graph::new();
+    let name = match member {
+        Some(name) => name,
+        None if members.len() == 1 => members[0],
+        None => {
+            return Err(Error::ArchiveMember {
+                name: None,
+                members: members.iter().map(|name| name.to_string()).collect(),
+            })
+        }
+    };
+    if parsed.get(name).is_none() {
+        return Err(Error::ArchiveMember {
+            name: Some(name.to_string()),
+            members: members.iter().map(|name| name.to_string()).collect(),
+        });
+    }
 
-    graph = graph::split_to_basic_blocks(assembly_line, graph);
+    Ok(parsed.extract(name, archive)?.to_vec())
+}
 
-    graph = graph::determine_edges(graph);
+//loadable segment of the binary
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub virtual_address: u64,
+    pub memory_size: u64,
+    pub file_offset: u64,
+    pub file_size: u64,
+    pub executable: bool,
+}
 
-    print_graph(&graph);
+impl Segment {
+    //sizes come from the headers and the address can be rebased, the end may not fit in a u64
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.virtual_address && address - self.virtual_address < self.memory_size
+    }
+}
 
-    create_dot(&graph);
+//named section of the binary, stripped binaries can have none
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    pub file_offset: u64,
 }
 
-//function to create the dot file
-pub fn create_dot(graph: &graph::Graph) {
-    let mut cfg = Graph::<_, ()>::new();
+//loaded binary with everything that is shared between the cfgs of its functions
+pub struct Binary {
+    pub format: Format,
+    pub bytes: Vec<u8>,
+    //32 for i386, 64 for x86-64, 16 for real mode raw code, the mode the code is decoded in
+    pub bitness: u32,
+    pub entry: u64,
+    pub segments: Vec<Segment>,
+    pub sections: Vec<Section>,
+    pub symbols: Vec<symbols::Symbol>,
+    //global variables and GOT slots, only used to name memory operands
+    pub data_symbols: Vec<symbols::Symbol>,
+    pub fde_ranges: Vec<eh_frame::FdeRange>,
+    //addresses of functions that never return, see symbols::find_no_return_functions
+    pub no_return: HashSet<u64>,
+    //how symbol names are shown in the outputs, set it before building the cfgs
+    pub names: demangle::NameOptions,
+    //ET_DYN, a PIE or a shared object, the addresses in the file are relative to the load address
+    //for PE a DLL or an executable with ASLR (dynamic base), it is relocated away from image_base
+    //for Mach-O an MH_PIE executable or a dylib
+    pub position_independent: bool,
+    //ET_REL object file, its sections are laid out from 0 and its relocations are applied, see
+    //object::parse_object
+    pub relocatable: bool,
+    //address the first mapping of the binary starts at, the page of the lowest ELF segment, the
+    //ImageBase of a PE or __TEXT of a Mach-O, PE tables hold addresses relative to it (RVAs)
+    pub image_base: u64,
+    //what rebase added to every address of the file, 0 if the addresses are the file ones
+    pub bias: u64,
+}
 
-    for block in &graph.nodes {
-        cfg.add_node(block.convert_assembly_line_to_string());
+impl Binary {
+    //read and parse the binary at path
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Binary::load_member(path, None)
     }
-    for edge in &graph.edges {
-        cfg.add_edge(
-            NodeIndex::new(edge.node_indexes.0 as usize),
-            NodeIndex::new(edge.node_indexes.1 as usize),
-            (),
-        );
+
+    //read and parse the binary at path, if it is an ar archive (.a) the object file named member
+    //is parsed, member can be left out if the archive has only one
+    pub fn load_member(path: &Path, member: Option<&str>) -> Result<Self, Error> {
+        check_if_valid_binary(path)?;
+
+        let bytes = load_binary(path)?;
+
+        if bytes.starts_with(goblin::archive::MAGIC) {
+            return Binary::parse(extract_member(&bytes, member)?);
+        }
+        if let Some(member) = member {
+            log::warn!(
+                "{} is not an archive, member {} ignored",
+                path.display(),
+                member
+            );
+        }
+
+        Binary::parse(bytes)
+    }
+
+    //parse an ELF, PE or Mach-O file
+    pub fn parse(bytes: Vec<u8>) -> Result<Self, Error> {
+        match identify_format(&bytes)? {
+            Format::Elf => elf::parse_elf(bytes),
+            Format::Pe => pe::parse_pe(bytes),
+            Format::MachO => macho::parse_macho(bytes),
+            //raw code has no header it could be detected by
+            Format::Raw => Err(Error::UnknownFormat),
+        }
     }
 
-    let dot_output = format!("{:?}", Dot::with_config(&cfg, &[Config::EdgeNoLabel]));
+    //read the file at path as raw code, see Binary::raw
+    pub fn load_raw(
+        path: &Path,
+        bitness: u32,
+        load_address: u64,
+        entry_offset: u64,
+    ) -> Result<Self, Error> {
+        let bytes = load_binary(path)?;
 
-    let dot_output = dot_output.replace("\\n", "n");
+        Binary::raw(bytes, bitness, load_address, entry_offset)
+    }
+
+    //code without headers, the whole file is mapped at load_address and decoded in the mode of
+    //bitness (16, 32 or 64), the entry point is entry_offset bytes into the file
+    //there are no symbols or function ranges, functions are followed from their start address
+    pub fn raw(
+        bytes: Vec<u8>,
+        bitness: u32,
+        load_address: u64,
+        entry_offset: u64,
+    ) -> Result<Self, Error> {
+        if !matches!(bitness, 16 | 32 | 64) {
+            return Err(Error::InvalidBitness(bitness));
+        }
+        let size = bytes.len() as u64;
+        let entry = load_address.wrapping_add(entry_offset);
+        if entry_offset >= size {
+            return Err(Error::AddressUnmapped(entry));
+        }
+        //the ip of 16-bit code is 16 bits, the decoder wraps branch targets at 64K
+        if bitness == 16 && load_address + size > 0x10000 {
+            log::warn!(
+                "16-bit code above 0xFFFF, the branch targets wrap around at 64K, use the offset \
+                 into the code segment as --base"
+            );
+        }
+
+        Ok(Binary {
+            format: Format::Raw,
+            bytes,
+            bitness,
+            entry,
+            segments: vec![Segment {
+                virtual_address: load_address,
+                memory_size: size,
+                file_offset: 0,
+                file_size: size,
+                executable: true,
+            }],
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            data_symbols: Vec::new(),
+            fde_ranges: Vec::new(),
+            no_return: HashSet::new(),
+            names: demangle::NameOptions::default(),
+            position_independent: false,
+            relocatable: false,
+            image_base: load_address,
+            bias: 0,
+        })
+    }
 
-    let dot_output = dot_output.replace(
-        "{",
-        "{
-    node[
-        shape=box,
-    ]
-    edge[
-        tailport=s,
-        headport=n,
-    ]",
-    );
+    //move the binary to the address it was loaded at, base is the start of its first mapping
+    //like in /proc/pid/maps, after that every address in and out of the binary is a runtime one
+    pub fn rebase(&mut self, base: u64) {
+        let shift = base.wrapping_sub(self.image_base);
+        self.bias = self.bias.wrapping_add(shift);
+        self.image_base = base;
 
-    std::fs::write("output.dot", dot_output).expect("Failed to write Dot output to file");
+        self.entry = self.entry.wrapping_add(shift);
+        for segment in &mut self.segments {
+            segment.virtual_address = segment.virtual_address.wrapping_add(shift);
+        }
+        //sections that arent loaded have no address
+        for section in self
+            .sections
+            .iter_mut()
+            .filter(|section| section.address != 0)
+        {
+            section.address = section.address.wrapping_add(shift);
+        }
+        for symbol in self.symbols.iter_mut().chain(&mut self.data_symbols) {
+            symbol.address = symbol.address.wrapping_add(shift);
+        }
+        for range in &mut self.fde_ranges {
+            range.start = range.start.wrapping_add(shift);
+            range.end = range.end.wrapping_add(shift);
+        }
+        self.no_return = self
+            .no_return
+            .iter()
+            .map(|address| address.wrapping_add(shift))
+            .collect();
+    }
+
+    //hex digits of a full address, addresses in listings are padded to it
+    //16-bit code has 20-bit real mode addresses, it gets the width of 32-bit code
+    pub fn address_width(&self) -> usize {
+        if self.bitness == 64 {
+            16
+        } else {
+            8
+        }
+    }
+
+    //true if address is inside one of the loadable segments
+    pub fn is_mapped(&self, address: u64) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| segment.contains(address))
+    }
+
+    //translate a virtual address into a file offset using the loadable segments
+    //returns None if the address is not backed by bytes in the file (unmapped or .bss)
+    pub fn virtual_address_to_offset(&self, address: u64) -> Option<u64> {
+        calculate_offset(&self.segments, &address).ok()
+    }
+
+    //the raw bytes at address, None if they arent all backed by the file
+    pub fn bytes_at(&self, address: u64, length: usize) -> Option<&[u8]> {
+        let offset = self.virtual_address_to_offset(address)? as usize;
+        self.bytes.get(offset..offset.checked_add(length)?)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    //turn a hex address with 0x prefix or a (partial) function name into an address, mangled
+    //and demangled names both work
+    //after a rebase a hex address can still be given as a file address, it is moved as well
+    //for PE an unmapped address is tried as an RVA, the way dumpbin and crash dumps show them, and
+    //for raw code as an offset into the file
+    pub fn resolve_address(&self, input: &str) -> Result<u64, Error> {
+        let address = symbols::resolve_address(&self.symbols, input)?;
+        if self.is_mapped(address) {
+            return Ok(address);
+        }
+        let moved = address.wrapping_add(self.bias);
+        if self.bias != 0 && self.is_mapped(moved) {
+            log::info!(
+                "{:#X} is a file address, it is {:#X} at runtime",
+                address,
+                moved
+            );
+            return Ok(moved);
+        }
+        let relative = address.wrapping_add(self.image_base);
+        if matches!(self.format, Format::Pe | Format::Raw) && self.is_mapped(relative) {
+            log::info!(
+                "{:#X} is relative to the image base, it is {:#X}",
+                address,
+                relative
+            );
+            return Ok(relative);
+        }
+        Ok(address)
+    }
+
+    //symbol name the way the outputs show it, see names
+    pub fn display_name(&self, name: &str) -> String {
+        demangle::demangle(name, &self.names)
+    }
+
+    //range of the function containing address, see bounds::find_function_bounds
+    pub fn function_bounds(&self, address: u64) -> bounds::FunctionBounds {
+        bounds::find_function_bounds(&self.segments, &self.symbols, &self.fde_ranges, address)
+    }
+
+    //name of the function at address, functions without a symbol get an IDA style sub_ name
+    pub fn function_name(&self, address: u64) -> String {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.address == address)
+            .map(|symbol| self.display_name(&symbol.name))
+            .unwrap_or_else(|| format!("sub_{:X}", address))
+    }
+
+    //mangled name of the imported function if address is a PLT stub, a GOT slot or a PE import
+    //address table slot
+    pub fn import_name(&self, address: u64) -> Option<&str> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.address == address)
+            .find_map(|symbol| symbol.name.strip_suffix("@plt"))
+            .or_else(|| {
+                self.data_symbols
+                    .iter()
+                    .filter(|symbol| symbol.address == address)
+                    .find_map(|symbol| {
+                        symbol
+                            .name
+                            .strip_suffix("@got")
+                            .or_else(|| symbol.name.strip_prefix("__imp_"))
+                    })
+            })
+    }
 }
 
-/* node[
-    shape=box,
-]
-edge[
-    tailport=s,
-    headport=n,
-] */
+//write callgraph.dot and callgraph.json into output_dir
+pub fn write_call_graph(call_graph: &callgraph::CallGraph, output_dir: &Path) -> Result<(), Error> {
+    for (file_name, content) in [
+        (
+            "callgraph.dot",
+            callgraph::create_call_graph_dot(call_graph),
+        ),
+        (
+            "callgraph.json",
+            callgraph::create_call_graph_json(call_graph),
+        ),
+    ] {
+        let path = output_dir.join(file_name);
+        std::fs::write(&path, content).map_err(|err| Error::io(&path, err))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::CfgBuilder;
+    use goblin::elf::header::{EM_386, EM_X86_64};
+    use iced_x86::{Formatter, NasmFormatter};
+
+    // 0x100 mov rax,rcx in 64-bit mode, dec eax and mov eax,ecx in 32-bit mode
+    // 0x103 xor eax,eax
+    // 0x105 cmp eax,10
+    // 0x108 jge 0x10f
+    // 0x10a add eax,1
+    // 0x10d jmp 0x105
+    // 0x10f ret
+    const CODE: &[u8] = &[
+        0x48, 0x89, 0xC8, 0x31, 0xC0, 0x83, 0xF8, 0x0A, 0x7D, 0x05, 0x83, 0xC0, 0x01, 0xEB, 0xF6,
+        0xC3,
+    ];
+    const CODE_OFFSET: u64 = 0x100;
+
+    //append values to image as little endian integers of size bytes, the image builders write
+    //their header fields with it
+    fn put(image: &mut Vec<u8>, size: usize, values: &[u64]) {
+        for value in values {
+            image.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+    }
+
+    //name padded to the 16 bytes of a Mach-O segment or section name
+    fn put_name(image: &mut Vec<u8>, name: &str) {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(16, 0);
+        image.extend_from_slice(&bytes);
+    }
+
+    //executable with a single loadable segment that maps the whole file at base, CODE is at
+    //base + CODE_OFFSET and is the entry point
+    fn elf_image(is_64: bool, machine: u16, base: u64) -> Vec<u8> {
+        let word = if is_64 { 8 } else { 4 };
+        let (header_size, program_header_size) = if is_64 { (64, 56) } else { (52, 32) };
+        let file_size = CODE_OFFSET + CODE.len() as u64;
+
+        let mut image = vec![0x7F, b'E', b'L', b'F', if is_64 { 2 } else { 1 }, 1, 1];
+        image.resize(16, 0);
+        //e_type EXEC, e_machine, e_version
+        put(&mut image, 2, &[2, u64::from(machine)]);
+        put(&mut image, 4, &[1]);
+        //e_entry, e_phoff, e_shoff
+        put(&mut image, word, &[base + CODE_OFFSET, header_size, 0]);
+        //e_flags, e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+        put(&mut image, 4, &[0]);
+        put(
+            &mut image,
+            2,
+            &[header_size, program_header_size, 1, 0, 0, 0],
+        );
+
+        //PT_LOAD, p_flags R+X sits right after p_type in ELF64 and at the end in ELF32
+        put(&mut image, 4, &[1]);
+        if is_64 {
+            put(&mut image, 4, &[5]);
+        }
+        //p_offset, p_vaddr, p_paddr, p_filesz, p_memsz
+        put(&mut image, word, &[0, base, base, file_size, file_size]);
+        if !is_64 {
+            put(&mut image, 4, &[5]);
+        }
+        put(&mut image, word, &[0x1000]);
+
+        image.resize(CODE_OFFSET as usize, 0);
+        image.extend_from_slice(CODE);
+        image
+    }
+
+    //dynamically linked ELF64 built with -fno-plt, the code at 0x100 calls __stack_chk_fail
+    //through its GOT slot at 0x200 that a GLOB_DAT relocation names
+    // 0x100 test edi,edi
+    // 0x102 jne 0x10a
+    // 0x104 call [rip+0xf6]
+    // 0x10a ret
+    fn no_plt_elf_image(base: u64) -> Vec<u8> {
+        let mut image = vec![0x7F, b'E', b'L', b'F', 2, 1, 1];
+        image.resize(16, 0);
+        put(&mut image, 2, &[2, u64::from(EM_X86_64)]);
+        put(&mut image, 4, &[1]);
+        put(&mut image, 8, &[base + 0x100, 64, 0]);
+        put(&mut image, 4, &[0]);
+        put(&mut image, 2, &[64, 56, 2, 0, 0, 0]);
+
+        //PT_LOAD of the whole file and PT_DYNAMIC: type, flags, offset, vaddr, paddr, filesz,
+        //memsz, align
+        put(&mut image, 4, &[1, 7]);
+        put(&mut image, 8, &[0, base, base, 0x298, 0x298, 0x1000]);
+        put(&mut image, 4, &[2, 6]);
+        put(
+            &mut image,
+            8,
+            &[0x180, base + 0x180, base + 0x180, 0x80, 0x80, 8],
+        );
+
+        image.resize(0x100, 0);
+        image.extend_from_slice(&[0x85, 0xFF, 0x75, 0x06, 0xFF, 0x15, 0xF6, 0, 0, 0, 0xC3]);
+        //DT_STRTAB, DT_STRSZ, DT_SYMTAB, DT_SYMENT, DT_RELA, DT_RELASZ, DT_RELAENT, DT_NULL
+        image.resize(0x180, 0);
+        for (tag, value) in [
+            (5, base + 0x220),
+            (10, 18),
+            (6, base + 0x240),
+            (11, 24),
+            (7, base + 0x280),
+            (8, 24),
+            (9, 24),
+            (0, 0),
+        ] {
+            put(&mut image, 8, &[tag, value]);
+        }
+        //the GOT slot is 0x200
+        image.resize(0x220, 0);
+        image.extend_from_slice(b"\0__stack_chk_fail\0");
+        //.dynsym: null, __stack_chk_fail global undefined function
+        image.resize(0x258, 0);
+        put(&mut image, 4, &[1]);
+        image.extend_from_slice(&[0x12, 0]);
+        put(&mut image, 2, &[0]);
+        put(&mut image, 8, &[0, 0]);
+        //R_X86_64_GLOB_DAT of symbol 1 at the slot
+        image.resize(0x280, 0);
+        put(&mut image, 8, &[base + 0x200, (1 << 32) | 6, 0]);
+        image
+    }
+
+    //PE32+ with CODE at the start of .text (RVA 0x1000, the entry point) and a .pdata
+    //RUNTIME_FUNCTION covering it
+    fn pe_image(image_base: u64) -> Vec<u8> {
+        //MZ header, e_lfanew points right behind it
+        let mut image = b"MZ".to_vec();
+        image.resize(0x3C, 0);
+        put(&mut image, 4, &[0x40]);
+        image.extend_from_slice(b"PE\0\0");
+        //machine AMD64, 2 sections, no symbols, 240 bytes optional header, executable
+        put(&mut image, 2, &[0x8664, 2]);
+        put(&mut image, 4, &[0, 0, 0]);
+        put(&mut image, 2, &[240, 0x22]);
+
+        //PE32+ magic and linker version, code sizes, entry, base of code
+        put(&mut image, 2, &[0x20B, 0]);
+        put(&mut image, 4, &[0x200, 0x200, 0, 0x1000, 0x1000]);
+        put(&mut image, 8, &[image_base]);
+        //section and file alignment, versions, size of image, size of headers, checksum
+        put(&mut image, 4, &[0x1000, 0x200]);
+        put(&mut image, 2, &[6, 0, 0, 0, 6, 0]);
+        put(&mut image, 4, &[0, 0x3000, 0x200, 0]);
+        //console subsystem, no dll characteristics, stack and heap sizes, loader flags
+        put(&mut image, 2, &[3, 0]);
+        image.extend_from_slice(&[0; 32]);
+        put(&mut image, 4, &[0, 16]);
+        //data directories, only the exception table (index 3) is set
+        for directory in 0..16 {
+            if directory == 3 {
+                put(&mut image, 4, &[0x2000, 12]);
+            } else {
+                put(&mut image, 4, &[0, 0]);
+            }
+        }
+
+        //name, virtual size and address, raw size and pointer, relocations, characteristics
+        for (name, address, raw, characteristics) in [
+            (b".text\0\0\0", 0x1000, 0x200, 0x6000_0020),
+            (b".pdata\0\0", 0x2000, 0x400, 0x4000_0040),
+        ] {
+            image.extend_from_slice(name);
+            put(&mut image, 4, &[0x10, address, 0x200, raw, 0, 0, 0]);
+            put(&mut image, 4, &[characteristics]);
+        }
+
+        image.resize(0x200, 0);
+        image.extend_from_slice(CODE);
+        image.resize(0x400, 0);
+        put(&mut image, 4, &[0x1000, 0x1000 + CODE.len() as u64, 0x2010]);
+        image.resize(0x600, 0);
+        image
+    }
+
+    //x86_64 MH_EXECUTE with CODE in __text at base + 0x200 (main, the entry point) followed by a
+    //__stubs entry for exit, LC_FUNCTION_STARTS has main
+    fn macho_image(base: u64) -> Vec<u8> {
+        //magic, x86_64, executable with 6 load commands, MH_PIE
+        let mut image = Vec::new();
+        put(
+            &mut image,
+            4,
+            &[0xFEEDFACF, 0x0100_0007, 3, 2, 6, 448, 0x20_0000, 0],
+        );
+
+        //LC_SEGMENT_64 __PAGEZERO and __TEXT: vmaddr, vmsize, fileoff, filesize, then
+        //maxprot, initprot, nsects, flags
+        put(&mut image, 4, &[0x19, 72]);
+        put_name(&mut image, "__PAGEZERO");
+        put(&mut image, 8, &[0, base, 0, 0]);
+        put(&mut image, 4, &[0, 0, 0, 0]);
+        put(&mut image, 4, &[0x19, 72 + 2 * 80]);
+        put_name(&mut image, "__TEXT");
+        put(&mut image, 8, &[base, 0x1000, 0, 0x1000]);
+        put(&mut image, 4, &[5, 5, 2, 0]);
+        //addr, size, then offset, align, reloff, nreloc, flags and reserved1-3
+        //reserved2 of __stubs is the stub size, reserved1 its first indirect symbol
+        for (section, address, size, flags, stub_size) in [
+            ("__text", 0x200, 0x10, 0x8000_0400, 0),
+            ("__stubs", 0x210, 6, 0x8000_0408, 6),
+        ] {
+            put_name(&mut image, section);
+            put_name(&mut image, "__TEXT");
+            put(&mut image, 8, &[base + address, size]);
+            put(&mut image, 4, &[address, 0, 0, 0, flags, 0, stub_size, 0]);
+        }
+        //LC_SYMTAB symoff, nsyms, stroff, strsize
+        put(&mut image, 4, &[0x2, 24, 0x300, 2, 0x340, 16]);
+        //LC_DYSYMTAB, only the indirect symbol table is set
+        put(
+            &mut image,
+            4,
+            &[0xB, 80, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0x330, 1],
+        );
+        put(&mut image, 4, &[0, 0, 0, 0]);
+        //LC_FUNCTION_STARTS dataoff, datasize
+        put(&mut image, 4, &[0x26, 16, 0x350, 8]);
+        //LC_MAIN entryoff, stacksize
+        put(&mut image, 4, &[0x8000_0028, 24]);
+        put(&mut image, 8, &[0x200, 0]);
+
+        image.resize(0x200, 0);
+        image.extend_from_slice(CODE);
+        //jmp [rip+0], the pointer isnt needed for the name
+        image.extend_from_slice(&[0xFF, 0x25, 0, 0, 0, 0]);
+
+        //nlist_64 strx, type, sect, desc and value: _main defined in section 1, _exit undefined
+        image.resize(0x300, 0);
+        put(&mut image, 4, &[1, 0x010F]);
+        put(&mut image, 8, &[base + 0x200]);
+        put(&mut image, 4, &[7, 0x01]);
+        put(&mut image, 8, &[0]);
+        //the indirect symbol of the stub is _exit
+        image.resize(0x330, 0);
+        put(&mut image, 4, &[1]);
+        image.resize(0x340, 0);
+        image.extend_from_slice(b"\0_main\0_exit\0");
+        //function starts, ULEB128 0x200 from __TEXT
+        image.resize(0x350, 0);
+        image.extend_from_slice(&[0x80, 0x04]);
+        image.resize(0x1000, 0);
+        image
+    }
+
+    //x86-64 object file, .text is CODE and main at 0x10 calling the undefined exit through an
+    //R_X86_64_PLT32 relocation
+    fn object_image() -> Vec<u8> {
+        let mut image = vec![0x7F, b'E', b'L', b'F', 2, 1, 1];
+        image.resize(16, 0);
+        //ET_REL, EM_X86_64, version, no entry or program headers, section headers at 0xF0
+        put(&mut image, 2, &[1, 62]);
+        put(&mut image, 4, &[1]);
+        put(&mut image, 8, &[0, 0, 0xF0]);
+        put(&mut image, 4, &[0]);
+        put(&mut image, 2, &[64, 0, 0, 64, 6, 5]);
+
+        //.text: CODE, then main: call exit, ret
+        image.extend_from_slice(CODE);
+        image.extend_from_slice(&[0xE8, 0, 0, 0, 0, 0xC3]);
+        //.rela.text: offset of the call displacement, symbol 2 R_X86_64_PLT32, addend -4
+        image.resize(0x58, 0);
+        put(&mut image, 8, &[0x11, (2 << 32) | 4, -4i64 as u64]);
+        //.symtab: null, main global function in section 1, exit global undefined
+        image.resize(0x88, 0);
+        put(&mut image, 4, &[1]);
+        image.extend_from_slice(&[0x12, 0]);
+        put(&mut image, 2, &[1]);
+        put(&mut image, 8, &[0x10, 6]);
+        put(&mut image, 4, &[6]);
+        image.extend_from_slice(&[0x10, 0]);
+        put(&mut image, 2, &[0]);
+        put(&mut image, 8, &[0, 0]);
+        image.extend_from_slice(b"\0main\0exit\0");
+        image.extend_from_slice(b"\0.text\0.rela.text\0.symtab\0.strtab\0.shstrtab\0");
+
+        //name, type, flags, address, offset, size, link, info, alignment, entry size
+        image.resize(0xF0, 0);
+        image.resize(0xF0 + 64, 0);
+        for (name, kind, flags, offset, size, link, info, align, entry_size) in [
+            (1, 1, 6, 0x40, 0x16, 0, 0, 16, 0),
+            (7, 4, 0x40, 0x58, 24, 3, 1, 8, 24),
+            (18, 2, 0, 0x70, 72, 4, 1, 8, 24),
+            (26, 3, 0, 0xB8, 11, 0, 0, 1, 0),
+            (34, 3, 0, 0xC3, 44, 0, 0, 1, 0),
+        ] {
+            put(&mut image, 4, &[name, kind]);
+            put(&mut image, 8, &[flags, 0, offset, size]);
+            put(&mut image, 4, &[link, info]);
+            put(&mut image, 8, &[align, entry_size]);
+        }
+        image
+    }
+
+    //build the cfg at the entry point and return it with the text of its first instruction
+    fn entry_cfg(binary: &Binary) -> (graph::Graph, String) {
+        let function = CfgBuilder::new(binary)
+            .address(binary.entry)
+            .build()
+            .unwrap();
+        let mut text = String::new();
+        NasmFormatter::new().format(&function.cfg.nodes[0].assembly_line[0].1, &mut text);
+        (function.cfg, text)
+    }
+
+    fn block_starts(graph: &graph::Graph) -> Vec<u64> {
+        graph.nodes.iter().map(|block| block.start_addr).collect()
+    }
+
+    fn edges(graph: &graph::Graph) -> Vec<((u64, u64), graph::EdgeKind)> {
+        let mut edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.node_indexes, edge.kind))
+            .collect();
+        edges.sort_by_key(|(indexes, _)| *indexes);
+        edges
+    }
+
+    //addresses reassemble decodes from the start of code, which is at 0x1000
+    fn decoded_addresses(
+        code: &[u8],
+        bounds: bounds::FunctionBounds,
+        no_return: &HashSet<u64>,
+    ) -> Vec<u64> {
+        reassemble(64, code, &0, &0x1000, &bounds, no_return)
+            .assembly_line
+            .iter()
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    fn heuristic(start: u64, end: u64) -> bounds::FunctionBounds {
+        bounds::FunctionBounds {
+            start,
+            end,
+            source: bounds::BoundsSource::Heuristic,
+        }
+    }
+
+    #[test]
+    fn follows_every_path_to_its_return() {
+        // 0x1000 test edi,edi
+        // 0x1002 je 0x100a
+        // 0x1004 mov eax,1
+        // 0x1009 ret
+        // 0x100a xor eax,eax
+        // 0x100c ret
+        // 0x100d int3 padding
+        let code = [
+            0x85, 0xFF, 0x74, 0x06, 0xB8, 0x01, 0, 0, 0, 0xC3, 0x31, 0xC0, 0xC3, 0xCC, 0xCC, 0xCC,
+        ];
+        assert_eq!(
+            decoded_addresses(&code, heuristic(0x1000, 0x1010), &HashSet::new()),
+            vec![0x1000, 0x1002, 0x1004, 0x1009, 0x100A, 0x100C]
+        );
+    }
+
+    #[test]
+    fn skips_unreachable_bytes() {
+        // 0x1000 test edi,edi
+        // 0x1002 jne 0x1006
+        // 0x1004 ret
+        // 0x1005 int3, between the ret and the cold block
+        // 0x1006 call 0x2000, doesnt return
+        // 0x100b mov eax,1
+        // 0x1010 ret
+        let code = [
+            0x85, 0xFF, 0x75, 0x02, 0xC3, 0xCC, 0xE8, 0xF5, 0x0F, 0, 0, 0xB8, 0x01, 0, 0, 0, 0xC3,
+        ];
+        let bounds = heuristic(0x1000, 0x1011);
+        assert_eq!(
+            decoded_addresses(&code, bounds, &HashSet::from([0x2000])),
+            vec![0x1000, 0x1002, 0x1004, 0x1006]
+        );
+        //a call that returns falls through
+        assert_eq!(
+            decoded_addresses(&code, bounds, &HashSet::new()),
+            vec![0x1000, 0x1002, 0x1004, 0x1006, 0x100B, 0x1010]
+        );
+    }
+
+    #[test]
+    fn fills_gaps_of_authoritative_bounds() {
+        // 0x1000 ret
+        // 0x1001 invalid in 64-bit mode
+        // 0x1002 mov eax,1, only reachable through a jump table
+        // 0x1007 ret
+        let code = [0xC3, 0x06, 0xB8, 0x01, 0, 0, 0, 0xC3];
+        let symbol = bounds::FunctionBounds {
+            start: 0x1000,
+            end: 0x1008,
+            source: bounds::BoundsSource::Symbol,
+        };
+        assert_eq!(
+            decoded_addresses(&code, symbol, &HashSet::new()),
+            vec![0x1000, 0x1002, 0x1007]
+        );
+        //guessed bounds only get the reachable code
+        assert_eq!(
+            decoded_addresses(&code, heuristic(0x1000, 0x1008), &HashSet::new()),
+            vec![0x1000]
+        );
+    }
+
+    #[test]
+    fn reports_escapes_but_not_tail_jumps() {
+        // 0x1000 test edi,edi
+        // 0x1002 jne 0x1010, outside the function
+        // 0x1004 xor eax,eax, runs into the next function at 0x1006
+        // 0x1006 ret
+        let code = [0x85, 0xFF, 0x75, 0x0C, 0x31, 0xC0, 0xC3];
+        let reassembly = reassemble(
+            64,
+            &code,
+            &0,
+            &0x1000,
+            &heuristic(0x1000, 0x1006),
+            &HashSet::new(),
+        );
+        assert_eq!(reassembly.assembly_line.len(), 3);
+        assert_eq!(reassembly.escapes, vec![(0x1002, 0x1010), (0x1004, 0x1006)]);
+
+        //jmp 0x2000
+        let code = [0xE9, 0xFB, 0x0F, 0, 0];
+        let reassembly = reassemble(
+            64,
+            &code,
+            &0,
+            &0x1000,
+            &heuristic(0x1000, 0x1005),
+            &HashSet::new(),
+        );
+        assert!(reassembly.escapes.is_empty());
+    }
+
+    #[test]
+    fn parses_elf64() {
+        let binary = Binary::parse(elf_image(true, EM_X86_64, 0x400000)).unwrap();
+        assert_eq!(binary.bitness, 64);
+        assert_eq!(binary.address_width(), 16);
+        assert_eq!(binary.entry, 0x400100);
+
+        let (cfg, first) = entry_cfg(&binary);
+        assert_eq!(first, "mov rax,rcx");
+        assert_eq!(
+            block_starts(&cfg),
+            vec![0x400100, 0x400105, 0x40010A, 0x40010F]
+        );
+        assert_eq!(cfg.edges.len(), 4);
+    }
+
+    #[test]
+    fn no_return_call_through_the_got_ends_its_block() {
+        let binary = Binary::parse(no_plt_elf_image(0x400000)).unwrap();
+        assert_eq!(binary.import_name(0x400200), Some("__stack_chk_fail"));
+        assert!(binary.no_return.contains(&0x400200));
+
+        //the call doesnt fall through into the ret
+        let (cfg, _) = entry_cfg(&binary);
+        assert_eq!(block_starts(&cfg), vec![0x400100, 0x400104, 0x40010A]);
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                ((0, 1), graph::EdgeKind::NotTaken),
+                ((0, 2), graph::EdgeKind::Taken)
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_elf32() {
+        let binary = Binary::parse(elf_image(false, EM_386, 0x8048000)).unwrap();
+        assert_eq!(binary.bitness, 32);
+        assert_eq!(binary.address_width(), 8);
+        assert_eq!(binary.entry, 0x8048100);
+
+        let (cfg, first) = entry_cfg(&binary);
+        assert_eq!(first, "dec eax");
+        assert_eq!(cfg.nodes[0].assembly_line.len(), 3);
+        assert_eq!(
+            block_starts(&cfg),
+            vec![0x8048100, 0x8048105, 0x804810A, 0x804810F]
+        );
+        assert_eq!(cfg.edges.len(), 4);
+    }
+
+    #[test]
+    fn rejects_other_classes_and_machines() {
+        let mut image = elf_image(true, EM_X86_64, 0x400000);
+        image[4] = 3;
+        assert!(matches!(Binary::parse(image), Err(Error::WrongClass(3))));
+
+        //EM_ARM
+        let image = elf_image(false, 40, 0x8000);
+        assert!(matches!(
+            Binary::parse(image),
+            Err(Error::UnsupportedMachine(40))
+        ));
+    }
+
+    #[test]
+    fn rebases_pies() {
+        let mut image = elf_image(true, EM_X86_64, 0);
+        //ET_DYN
+        image[16] = 3;
+        let mut binary = Binary::parse(image).unwrap();
+        assert!(binary.position_independent);
+        assert_eq!(binary.entry, 0x100);
+
+        let base = 0x5555_5555_4000;
+        binary.rebase(base);
+        assert_eq!((binary.image_base, binary.bias), (base, base));
+        assert_eq!(binary.entry, base + 0x100);
+        assert_eq!(binary.virtual_address_to_offset(base + 0x105), Some(0x105));
+
+        //file addresses and runtime addresses both work as input, the output is runtime
+        assert_eq!(binary.resolve_address("0x105").unwrap(), base + 0x105);
+        let runtime = format!("{:#x}", base + 0x105);
+        assert_eq!(binary.resolve_address(&runtime).unwrap(), base + 0x105);
+        let (cfg, _) = entry_cfg(&binary);
+        assert_eq!(
+            block_starts(&cfg),
+            vec![base + 0x100, base + 0x105, base + 0x10A, base + 0x10F]
+        );
+        assert_eq!(cfg.nodes[1].jmp_addr, Some(base + 0x10F));
+
+        //and back to the file addresses
+        binary.rebase(0);
+        assert_eq!((binary.image_base, binary.bias), (0, 0));
+        assert_eq!(binary.entry, 0x100);
+    }
+
+    #[test]
+    fn parses_pe64() {
+        let binary = Binary::parse(pe_image(0x140000000)).unwrap();
+        assert_eq!(binary.format, Format::Pe);
+        assert_eq!(binary.bitness, 64);
+        assert_eq!(binary.entry, 0x140001000);
+        assert_eq!(binary.virtual_address_to_offset(0x140001005), Some(0x205));
+
+        //.pdata is authoritative like .eh_frame
+        assert_eq!(binary.fde_ranges.len(), 1);
+        let bounds = binary.function_bounds(binary.entry);
+        assert_eq!((bounds.start, bounds.end), (0x140001000, 0x140001010));
+        assert_eq!(bounds.source, bounds::BoundsSource::EhFrame);
+
+        //RVAs are accepted as addresses
+        assert_eq!(binary.resolve_address("0x1005").unwrap(), 0x140001005);
+
+        let (cfg, first) = entry_cfg(&binary);
+        assert_eq!(first, "mov rax,rcx");
+        assert_eq!(
+            block_starts(&cfg),
+            vec![0x140001000, 0x140001005, 0x14000100A, 0x14000100F]
+        );
+    }
+
+    #[test]
+    fn parses_macho() {
+        let base = 0x100000000;
+        let thin = macho_image(base);
+
+        //the slice of a fat binary starts at 0x1000 after the big endian fat header
+        let mut fat = Vec::new();
+        for value in [0xCAFEBABE, 1, 0x0100_0007, 3, 0x1000, thin.len() as u32, 12] {
+            fat.extend_from_slice(&u32::to_be_bytes(value));
+        }
+        fat.resize(0x1000, 0);
+        fat.extend_from_slice(&thin);
+
+        for (image, slice_offset) in [(thin, 0), (fat, 0x1000)] {
+            let binary = Binary::parse(image).unwrap();
+            assert_eq!(binary.format, Format::MachO);
+            assert_eq!(binary.bitness, 64);
+            assert_eq!(binary.entry, base + 0x200);
+            assert_eq!(binary.image_base, base);
+            assert!(binary.position_independent);
+            //__PAGEZERO isnt mapped
+            assert!(!binary.is_mapped(0x1000));
+            assert_eq!(
+                binary.virtual_address_to_offset(base + 0x200),
+                Some(slice_offset + 0x200)
+            );
+
+            assert_eq!(binary.function_name(binary.entry), "main");
+            let bounds = binary.function_bounds(binary.entry);
+            assert_eq!((bounds.start, bounds.end), (base + 0x200, base + 0x210));
+            assert_eq!(binary.import_name(base + 0x210), Some("exit"));
+            assert!(binary.no_return.contains(&(base + 0x210)));
+
+            let (cfg, first) = entry_cfg(&binary);
+            assert_eq!(first, "mov rax,rcx");
+            assert_eq!(cfg.nodes.len(), 4);
+        }
+    }
+
+    #[test]
+    fn maps_segments_at_the_top_of_the_address_space() {
+        let segment = Segment {
+            virtual_address: 0xFFFF_FFFF_FFFF_F000,
+            memory_size: 0x2000,
+            file_offset: 0x200,
+            file_size: 0x2000,
+            executable: true,
+        };
+        assert!(segment.contains(0xFFFF_FFFF_FFFF_F000));
+        assert!(segment.contains(u64::MAX));
+        assert!(!segment.contains(0));
+        assert!(!segment.contains(0xFFF));
+        let segments = [segment];
+        assert_eq!(calculate_offset(&segments, &u64::MAX).unwrap(), 0x11FF);
+        assert!(calculate_offset(&segments, &0x10).is_err());
+    }
+
+    #[test]
+    fn parses_raw() {
+        let binary = Binary::raw(CODE.to_vec(), 32, 0x1000, 3).unwrap();
+        assert_eq!(binary.format, Format::Raw);
+        assert_eq!(binary.entry, 0x1003);
+        //addresses outside the code are offsets into the file
+        assert_eq!(binary.resolve_address("0x5").unwrap(), 0x1005);
+        assert!(matches!(
+            Binary::raw(CODE.to_vec(), 32, 0x1000, 0x10),
+            Err(Error::AddressUnmapped(0x1010))
+        ));
+        assert!(matches!(
+            Binary::raw(CODE.to_vec(), 8, 0, 0),
+            Err(Error::InvalidBitness(8))
+        ));
+
+        //boot sector: cli, jmp $ in real mode
+        let binary = Binary::raw(vec![0xFA, 0xEB, 0xFE], 16, 0x7C00, 0).unwrap();
+        let (cfg, first) = entry_cfg(&binary);
+        assert_eq!(first, "cli");
+        assert_eq!(block_starts(&cfg), vec![0x7C00, 0x7C01]);
+        assert_eq!(cfg.edges.len(), 2);
+
+        //the default base is 0, a loop at the start of the file branches to address 0
+        let binary = Binary::raw(vec![0xFF, 0xCF, 0x75, 0xFC, 0xC3], 64, 0, 0).unwrap();
+        let (cfg, _) = entry_cfg(&binary);
+        assert_eq!(block_starts(&cfg), vec![0, 4]);
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                ((0, 0), graph::EdgeKind::Taken),
+                ((0, 1), graph::EdgeKind::NotTaken)
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_object_in_archive() {
+        let object = object_image();
+        //ar archive with the object as its only member
+        let mut archive = b"!<arch>\n".to_vec();
+        archive.extend_from_slice(
+            format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                "test.o/",
+                0,
+                0,
+                0,
+                644,
+                object.len()
+            )
+            .as_bytes(),
+        );
+        archive.extend_from_slice(&object);
+
+        let binary = Binary::parse(extract_member(&archive, None).unwrap()).unwrap();
+        assert!(binary.relocatable);
+        assert_eq!(binary.entry, 0);
+        assert!(matches!(
+            extract_member(&archive, Some("other.o")),
+            Err(Error::ArchiveMember { .. })
+        ));
+
+        //the call goes to the extern slot of exit behind .text
+        let main = binary.resolve_address("main").unwrap();
+        assert_eq!(main, 0x10);
+        assert_eq!(binary.import_name(0x20), Some("exit"));
+        let function = CfgBuilder::new(&binary).address(main).build().unwrap();
+        let call = function.cfg.nodes[0].assembly_line[0].1;
+        assert_eq!(call.near_branch_target(), 0x20);
+        assert!(binary.no_return.contains(&0x20));
+    }
+
+    #[test]
+    fn skips_relocations_outside_the_file() {
+        for r_offset in [u64::MAX - 1, 0x1000] {
+            let mut object = object_image();
+            object[0x58..0x60].copy_from_slice(&r_offset.to_le_bytes());
+            let binary = Binary::parse(object).unwrap();
+            //the call of main keeps its unrelocated target, the next instruction
+            let function = CfgBuilder::new(&binary).address(0x10).build().unwrap();
+            let call = function.cfg.nodes[0].assembly_line[0].1;
+            assert_eq!(call.near_branch_target(), 0x15);
+        }
+    }
+
+    #[test]
+    fn branches_to_the_start_of_an_object() {
+        //spin: dec edi, jnz spin, ret at .text offset 0, padded with int3 up to main
+        let mut object = object_image();
+        object[0x40..0x50].fill(0xCC);
+        object[0x40..0x45].copy_from_slice(&[0xFF, 0xCF, 0x75, 0xFC, 0xC3]);
+        let binary = Binary::parse(object).unwrap();
+
+        let (cfg, first) = entry_cfg(&binary);
+        assert_eq!(first, "dec edi");
+        assert_eq!(cfg.nodes[0].jmp_addr, Some(0));
+        assert_eq!(block_starts(&cfg), vec![0, 4]);
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                ((0, 0), graph::EdgeKind::Taken),
+                ((0, 1), graph::EdgeKind::NotTaken)
+            ]
+        );
+    }
+}
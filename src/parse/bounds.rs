@@ -0,0 +1,150 @@
+use super::eh_frame::{find_fde_range, FdeRange};
+use super::symbols::Symbol;
+use super::Segment;
+
+//where the function range came from, sources are tried in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsSource {
+    //st_value and st_size of a function symbol
+    Symbol,
+    //pc_begin and pc_range of an .eh_frame FDE, a .pdata RUNTIME_FUNCTION of a PE or the
+    //LC_FUNCTION_STARTS of a Mach-O, works for stripped binaries
+    EhFrame,
+    //guess: from the start address up to the next symbol or the end of the segment
+    Heuristic,
+}
+
+impl BoundsSource {
+    //stable identifier used in the machine readable exports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BoundsSource::Symbol => "symbol",
+            BoundsSource::EhFrame => "ehframe",
+            BoundsSource::Heuristic => "heuristic",
+        }
+    }
+}
+
+//address range [start, end) of a function
+#[derive(Debug, Clone, Copy)]
+pub struct FunctionBounds {
+    pub start: u64,
+    pub end: u64,
+    pub source: BoundsSource,
+}
+
+impl FunctionBounds {
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.start && address < self.end
+    }
+
+    //authoritative bounds are trusted to cover every byte of the function,
+    //so code which is not reachable from the start address is decoded as well
+    pub fn is_authoritative(&self) -> bool {
+        self.source != BoundsSource::Heuristic
+    }
+}
+
+//find the range of the function containing address, best source first
+pub fn find_function_bounds(
+    segments: &[Segment],
+    symbols: &[Symbol],
+    fde_ranges: &[FdeRange],
+    address: u64,
+) -> FunctionBounds {
+    if let Some(bounds) = symbol_bounds(symbols, address) {
+        return bounds;
+    }
+
+    if let Some(bounds) = eh_frame_bounds(fde_ranges, address) {
+        return bounds;
+    }
+
+    heuristic_bounds(segments, symbols, fde_ranges, address)
+}
+
+//the smallest sized function symbol covering the address
+//sizes come from the file, a symbol whose end overflows is corrupt and skipped
+pub fn symbol_bounds(symbols: &[Symbol], address: u64) -> Option<FunctionBounds> {
+    symbols
+        .iter()
+        .filter(|symbol| symbol.size != 0)
+        .filter_map(|symbol| Some((symbol, symbol.address.checked_add(symbol.size)?)))
+        .filter(|(symbol, end)| address >= symbol.address && address < *end)
+        .min_by_key(|(symbol, _)| symbol.size)
+        .map(|(symbol, end)| FunctionBounds {
+            start: symbol.address,
+            end,
+            source: BoundsSource::Symbol,
+        })
+}
+
+//the FDE range covering the address
+pub fn eh_frame_bounds(fde_ranges: &[FdeRange], address: u64) -> Option<FunctionBounds> {
+    find_fde_range(fde_ranges, address).map(|range| FunctionBounds {
+        start: range.start,
+        end: range.end,
+        source: BoundsSource::EhFrame,
+    })
+}
+
+//without any metadata the function is assumed to start at the given address
+//and to end at the next known function or at the end of the executable segment
+pub fn heuristic_bounds(
+    segments: &[Segment],
+    symbols: &[Symbol],
+    fde_ranges: &[FdeRange],
+    address: u64,
+) -> FunctionBounds {
+    let segment_end = segments
+        .iter()
+        .filter(|segment| segment.executable && segment.contains(address))
+        .map(|segment| segment.virtual_address.saturating_add(segment.memory_size))
+        .next()
+        .unwrap_or(u64::MAX);
+
+    let next_function = symbols
+        .iter()
+        .map(|symbol| symbol.address)
+        .chain(fde_ranges.iter().map(|range| range.start))
+        .filter(|function_address| *function_address > address)
+        .min()
+        .unwrap_or(u64::MAX);
+
+    FunctionBounds {
+        start: address,
+        end: segment_end.min(next_function),
+        source: BoundsSource::Heuristic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, address: u64, size: u64) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            address,
+            size,
+        }
+    }
+
+    #[test]
+    fn skips_symbols_whose_end_overflows() {
+        let symbols = [
+            symbol("outer", 0x1000, 0x100),
+            symbol("inner", 0x1010, 0x10),
+            symbol("corrupt", 0x1000, u64::MAX),
+        ];
+        let bounds = symbol_bounds(&symbols, 0x1018).unwrap();
+        assert_eq!((bounds.start, bounds.end), (0x1010, 0x1020));
+        let bounds = symbol_bounds(&symbols, 0x1080).unwrap();
+        assert_eq!((bounds.start, bounds.end), (0x1000, 0x1100));
+        assert!(symbol_bounds(&symbols, 0x2000).is_none());
+
+        //one past the end of the address space doesnt fit in a u64 either
+        let symbols = [symbol("last", u64::MAX - 0xF, 0x10)];
+        assert!(symbol_bounds(&symbols, u64::MAX - 1).is_none());
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+
+use iced_x86::FlowControl;
+use serde_json::json;
+
+use super::export::dot::escape_dot;
+use super::graph::{call_target, Graph};
+use super::Binary;
+use crate::cfg::CfgBuilder;
+use crate::error::Error;
+use crate::syntax::AsmFormatter;
+
+//a call instruction inside a function
+pub struct CallSite {
+    //address of the call instruction
+    pub address: u64,
+    //id of the basic block containing the call
+    pub block_id: u64,
+    //None for indirect calls like call rax or call [rbx+8], they cant be resolved statically
+    //a call through an import address table slot has the slot as target, see
+    //Binary::import_name
+    pub target: Option<u64>,
+    pub instruction: String,
+}
+
+pub struct CallGraphFunction {
+    pub address: u64,
+    pub name: String,
+    //PLT stub of a function imported from a shared library
+    pub import: bool,
+    //false if the function was not decoded, because its an import or beyond the max depth
+    pub expanded: bool,
+    //distance from the root, None if the call graph has no root
+    pub depth: Option<u32>,
+    pub call_sites: Vec<CallSite>,
+}
+
+pub struct CallGraph {
+    pub root: Option<u64>,
+    pub max_depth: Option<u32>,
+    //functions keyed by address
+    pub functions: BTreeMap<u64, CallGraphFunction>,
+}
+
+//collect every call instruction of a cfg of binary, the instruction texts are printed by the
+//formatter
+pub fn collect_call_sites(
+    binary: &Binary,
+    graph: &Graph,
+    formatter: &mut AsmFormatter,
+) -> Vec<CallSite> {
+    let mut call_sites = Vec::new();
+
+    for block in &graph.nodes {
+        for (address, instruction) in &block.assembly_line {
+            let target = match instruction.flow_control() {
+                FlowControl::Call => call_target(instruction),
+                //only import slots are known to hold a function address
+                FlowControl::IndirectCall => {
+                    call_target(instruction).filter(|slot| binary.import_name(*slot).is_some())
+                }
+                _ => continue,
+            };
+
+            call_sites.push(CallSite {
+                address: *address,
+                block_id: block.id,
+                target,
+                instruction: formatter.format(instruction),
+            });
+        }
+    }
+
+    call_sites
+}
+
+impl CallGraph {
+    pub fn new(root: Option<u64>, max_depth: Option<u32>) -> Self {
+        CallGraph {
+            root,
+            max_depth,
+            functions: BTreeMap::new(),
+        }
+    }
+
+    //add a decoded function with its call sites
+    pub fn add_function(
+        &mut self,
+        binary: &Binary,
+        address: u64,
+        depth: Option<u32>,
+        call_sites: Vec<CallSite>,
+    ) {
+        self.functions.insert(
+            address,
+            CallGraphFunction {
+                address,
+                name: binary.function_name(address),
+                import: false,
+                expanded: true,
+                depth,
+                call_sites,
+            },
+        );
+    }
+
+    //add a node for every call target that wasnt decoded, so every edge has both ends
+    //PLT stubs are named after the imported function
+    pub fn add_missing_targets(&mut self, binary: &Binary) {
+        let mut missing = BTreeMap::new();
+
+        for function in self.functions.values() {
+            for target in function.call_sites.iter().filter_map(|site| site.target) {
+                if !self.functions.contains_key(&target) {
+                    missing.insert(target, function.depth.map(|depth| depth + 1));
+                }
+            }
+        }
+
+        for (address, depth) in missing {
+            let import_name = binary.import_name(address);
+            self.functions.insert(
+                address,
+                CallGraphFunction {
+                    address,
+                    name: import_name
+                        .map(|name| binary.display_name(name))
+                        .unwrap_or_else(|| binary.function_name(address)),
+                    import: import_name.is_some(),
+                    expanded: false,
+                    depth,
+                    call_sites: Vec::new(),
+                },
+            );
+        }
+    }
+}
+
+//build the call graph reachable from root, breadth first
+//functions deeper than max_depth are shown as leaves but not decoded
+//fails if the cfg of root cant be built, callees that cant be built are only leaves
+pub fn build_call_graph(
+    binary: &Binary,
+    root: u64,
+    max_depth: Option<u32>,
+    formatter: &mut AsmFormatter,
+) -> Result<CallGraph, Error> {
+    let mut call_graph = CallGraph::new(Some(root), max_depth);
+
+    let mut queue = VecDeque::from([(root, 0)]);
+
+    while let Some((address, depth)) = queue.pop_front() {
+        if call_graph.functions.contains_key(&address) {
+            continue;
+        }
+        //imports have no code in this binary, they are added as leaves later
+        if binary.import_name(address).is_some() {
+            continue;
+        }
+        //callees that cant be built stay leaves
+        let function = match CfgBuilder::new(binary).address(address).build() {
+            Ok(function) => function,
+            Err(error) if depth == 0 => return Err(error),
+            Err(_) => continue,
+        };
+        let call_sites = collect_call_sites(binary, &function.cfg, formatter);
+
+        if max_depth.is_none_or(|max_depth| depth < max_depth) {
+            for target in call_sites.iter().filter_map(|site| site.target) {
+                queue.push_back((target, depth + 1));
+            }
+        }
+
+        call_graph.add_function(binary, address, Some(depth), call_sites);
+    }
+
+    call_graph.add_missing_targets(binary);
+
+    Ok(call_graph)
+}
+
+//one node per function, one edge per caller/callee pair labeled with the number of call sites
+//imports are dashed, functions with indirect calls list how many are unresolved
+pub fn create_call_graph_dot(call_graph: &CallGraph) -> String {
+    let mut dot = String::new();
+    dot += "digraph callgraph {\n";
+    dot += "    node[\n        shape=box,\n    ]\n";
+
+    for function in call_graph.functions.values() {
+        let mut label = escape_dot(&function.name);
+        let unresolved = function
+            .call_sites
+            .iter()
+            .filter(|site| site.target.is_none())
+            .count();
+        if unresolved != 0 {
+            write!(label, "\\n{} unresolved calls", unresolved).unwrap();
+        }
+
+        let mut style = Vec::new();
+        if function.import {
+            style.push("dashed");
+        }
+        if call_graph.root == Some(function.address) {
+            style.push("bold");
+        }
+
+        writeln!(
+            dot,
+            "    \"0x{:X}\" [ label = \"{}\" style = \"{}\" ]",
+            function.address,
+            label,
+            style.join(",")
+        )
+        .unwrap();
+    }
+
+    for function in call_graph.functions.values() {
+        //callee -> number of call sites
+        let mut callees: BTreeMap<u64, usize> = BTreeMap::new();
+        for target in function.call_sites.iter().filter_map(|site| site.target) {
+            *callees.entry(target).or_default() += 1;
+        }
+        for (callee, count) in callees {
+            writeln!(
+                dot,
+                "    \"0x{:X}\" -> \"0x{:X}\" [ label = \"{}\" ]",
+                function.address, callee, count
+            )
+            .unwrap();
+        }
+    }
+
+    dot += "}\n";
+    dot
+}
+
+//json with one entry per function, every call site is listed with its target or null if unresolved
+pub fn create_call_graph_json(call_graph: &CallGraph) -> String {
+    let functions: Vec<_> = call_graph
+        .functions
+        .values()
+        .map(|function| {
+            let call_sites: Vec<_> = function
+                .call_sites
+                .iter()
+                .map(|site| {
+                    json!({
+                        "address": site.address,
+                        "block": site.block_id,
+                        "target": site.target,
+                        "instruction": site.instruction,
+                    })
+                })
+                .collect();
+
+            json!({
+                "address": function.address,
+                "name": function.name,
+                "import": function.import,
+                "expanded": function.expanded,
+                "depth": function.depth,
+                "call_sites": call_sites,
+            })
+        })
+        .collect();
+
+    let call_graph_json = json!({
+        "root": call_graph.root,
+        "max_depth": call_graph.max_depth,
+        "functions": functions,
+    });
+
+    serde_json::to_string_pretty(&call_graph_json).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::symbols::{find_no_return_functions, Symbol};
+    use crate::syntax::FormatOptions;
+
+    //three functions calling each other in a cycle, 0x1000 -> 0x1010 -> 0x1020 -> 0x1000
+    fn cycle_binary() -> Binary {
+        let mut code = Vec::new();
+        for rel in [0x0Bi32, 0x0B, -0x25] {
+            code.push(0xE8);
+            code.extend_from_slice(&rel.to_le_bytes());
+            code.push(0xC3);
+            code.resize(code.len().next_multiple_of(0x10), 0xCC);
+        }
+        Binary::raw(code, 64, 0x1000, 0).unwrap()
+    }
+
+    fn callees(call_graph: &CallGraph, address: u64) -> Vec<u64> {
+        call_graph.functions[&address]
+            .call_sites
+            .iter()
+            .filter_map(|site| site.target)
+            .collect()
+    }
+
+    #[test]
+    fn follows_cycles_once() {
+        let binary = cycle_binary();
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let call_graph = build_call_graph(&binary, 0x1000, None, &mut formatter).unwrap();
+
+        let depths: Vec<(u64, Option<u32>, bool)> = call_graph
+            .functions
+            .values()
+            .map(|function| (function.address, function.depth, function.expanded))
+            .collect();
+        assert_eq!(
+            depths,
+            vec![
+                (0x1000, Some(0), true),
+                (0x1010, Some(1), true),
+                (0x1020, Some(2), true),
+            ]
+        );
+        assert_eq!(callees(&call_graph, 0x1020), vec![0x1000]);
+        assert!(create_call_graph_dot(&call_graph).contains("\"0x1020\" -> \"0x1000\""));
+    }
+
+    #[test]
+    fn stops_at_the_max_depth() {
+        let binary = cycle_binary();
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let call_graph = build_call_graph(&binary, 0x1000, Some(1), &mut formatter).unwrap();
+
+        //0x1010 is decoded, its callee is only a leaf
+        assert_eq!(callees(&call_graph, 0x1010), vec![0x1020]);
+        let leaf = &call_graph.functions[&0x1020];
+        assert!(!leaf.expanded);
+        assert_eq!(leaf.depth, Some(2));
+        assert!(leaf.call_sites.is_empty());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&create_call_graph_json(&call_graph)).unwrap();
+        assert_eq!(json["max_depth"], 1);
+        assert_eq!(json["functions"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn fails_if_the_root_cant_be_built() {
+        let binary = cycle_binary();
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        assert!(matches!(
+            build_call_graph(&binary, 0xDEADBEEF, None, &mut formatter),
+            Err(Error::AddressUnmapped(0xDEADBEEF))
+        ));
+    }
+
+    #[test]
+    fn calls_imports_through_their_slot() {
+        // 0x1000 call [rip+0xffa], the __imp_ExitProcess slot at 0x2000
+        // 0x1006 int3
+        let mut binary =
+            Binary::raw(vec![0xFF, 0x15, 0xFA, 0x0F, 0, 0, 0xCC], 64, 0x1000, 0).unwrap();
+        binary.data_symbols.push(Symbol {
+            name: "__imp_ExitProcess".to_string(),
+            address: 0x2000,
+            size: 8,
+        });
+        binary.no_return = find_no_return_functions(&binary.data_symbols);
+        assert_eq!(binary.import_name(0x2000), Some("ExitProcess"));
+
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let call_graph = build_call_graph(&binary, 0x1000, None, &mut formatter).unwrap();
+        assert_eq!(callees(&call_graph, 0x1000), vec![0x2000]);
+        let import = &call_graph.functions[&0x2000];
+        assert_eq!(import.name, "ExitProcess");
+        assert!(import.import && !import.expanded);
+
+        //ExitProcess doesnt return, the int3 isnt reached
+        let function = CfgBuilder::new(&binary).build().unwrap();
+        assert_eq!(function.cfg.nodes.len(), 1);
+    }
+
+    #[test]
+    fn calls_imports_through_their_got_slot() {
+        // 0x1000 call [rip+0xffa], the strcpy@got slot at 0x2000
+        // 0x1006 call [rip+0xffc], the exit@got slot at 0x2008
+        let mut binary = Binary::raw(
+            vec![0xFF, 0x15, 0xFA, 0x0F, 0, 0, 0xFF, 0x15, 0xFC, 0x0F, 0, 0],
+            64,
+            0x1000,
+            0,
+        )
+        .unwrap();
+        for (name, address) in [("strcpy@got", 0x2000), ("exit@got", 0x2008)] {
+            binary.data_symbols.push(Symbol {
+                name: name.to_string(),
+                address,
+                size: 8,
+            });
+        }
+        binary.no_return = find_no_return_functions(&binary.data_symbols);
+        assert_eq!(binary.import_name(0x2000), Some("strcpy"));
+
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let call_graph = build_call_graph(&binary, 0x1000, None, &mut formatter).unwrap();
+        assert_eq!(callees(&call_graph, 0x1000), vec![0x2000, 0x2008]);
+        for (address, name) in [(0x2000, "strcpy"), (0x2008, "exit")] {
+            let import = &call_graph.functions[&address];
+            assert_eq!(import.name, name);
+            assert!(import.import && !import.expanded);
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&create_call_graph_json(&call_graph)).unwrap();
+        let sites = json["functions"][0]["call_sites"].as_array().unwrap();
+        assert_eq!(sites.len(), 2);
+        assert!(sites.iter().all(|site| !site["target"].is_null()));
+    }
+}
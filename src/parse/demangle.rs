@@ -0,0 +1,94 @@
+//how symbol names are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameOptions {
+    //false shows the mangled names as they are in the symbol tables
+    pub demangle: bool,
+    //drop the ::h0123456789abcdef hash of rust legacy names and the crate disambiguators of v0
+    pub strip_hashes: bool,
+}
+
+impl Default for NameOptions {
+    fn default() -> Self {
+        NameOptions {
+            demangle: true,
+            strip_hashes: false,
+        }
+    }
+}
+
+//readable name of a mangled rust (legacy and v0) or c++ (itanium) symbol
+//suffixes like @plt or @got are kept, names that arent mangled are returned as they are
+pub fn demangle(name: &str, options: &NameOptions) -> String {
+    if !options.demangle {
+        return name.to_string();
+    }
+
+    let (mangled, suffix) = name.split_at(name.find('@').unwrap_or(name.len()));
+
+    //rust legacy names look like c++ ones, so rust goes first
+    if let Ok(demangled) = rustc_demangle::try_demangle(mangled) {
+        return if options.strip_hashes {
+            format!("{:#}{}", demangled, suffix)
+        } else {
+            format!("{}{}", demangled, suffix)
+        };
+    }
+    if mangled.starts_with("_Z") {
+        if let Ok(demangled) = cpp_demangle::Symbol::new(mangled)
+            .map_err(|_| std::fmt::Error)
+            .and_then(|symbol| symbol.demangle(&cpp_demangle::DemangleOptions::default()))
+        {
+            return format!("{}{}", demangled, suffix);
+        }
+    }
+
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRIPPED: NameOptions = NameOptions {
+        demangle: true,
+        strip_hashes: true,
+    };
+
+    #[test]
+    fn demangles_itanium() {
+        assert_eq!(
+            demangle("_ZNSt6vectorIiSaIiEE5beginEv@plt", &NameOptions::default()),
+            "std::vector<int, std::allocator<int> >::begin()@plt"
+        );
+    }
+
+    #[test]
+    fn demangles_rust_legacy() {
+        let name = "_ZN4core3fmt5write17h0123456789abcdefE";
+        assert_eq!(
+            demangle(name, &NameOptions::default()),
+            "core::fmt::write::h0123456789abcdef"
+        );
+        assert_eq!(demangle(name, &STRIPPED), "core::fmt::write");
+    }
+
+    #[test]
+    fn demangles_rust_v0() {
+        let name = "_RNvCs1234_7mycrate3foo";
+        assert_eq!(
+            demangle(name, &NameOptions::default()),
+            "mycrate[3c1c0]::foo"
+        );
+        assert_eq!(demangle(name, &STRIPPED), "mycrate::foo");
+    }
+
+    #[test]
+    fn keeps_raw_and_plain_names() {
+        let raw = NameOptions {
+            demangle: false,
+            strip_hashes: true,
+        };
+        assert_eq!(demangle("_ZN4core3fmt5writeE", &raw), "_ZN4core3fmt5writeE");
+        assert_eq!(demangle("main", &NameOptions::default()), "main");
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use goblin::elf::program_header::{PT_GNU_EH_FRAME, PT_LOAD};
+use goblin::elf::Elf;
+
+use crate::error::Error;
+
+//DW_EH_PE pointer encodings, low nibble is the format, high nibble how the value is applied
+const DW_EH_PE_OMIT: u8 = 0xFF;
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+const DW_EH_PE_ULEB128: u8 = 0x01;
+const DW_EH_PE_UDATA2: u8 = 0x02;
+const DW_EH_PE_UDATA4: u8 = 0x03;
+const DW_EH_PE_UDATA8: u8 = 0x04;
+const DW_EH_PE_SLEB128: u8 = 0x09;
+const DW_EH_PE_SDATA2: u8 = 0x0A;
+const DW_EH_PE_SDATA4: u8 = 0x0B;
+const DW_EH_PE_SDATA8: u8 = 0x0C;
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_DATAREL: u8 = 0x30;
+
+fn malformed(message: impl Into<String>) -> Error {
+    Error::EhFrame(message.into())
+}
+
+//code range [start, end) described by one FDE, the unwinder needs one per function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+//reads the little endian DWARF encoded values, address is the virtual address of bytes[0]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    address: u64,
+    //pointers are 8 bytes in ELF64 and 4 in ELF32
+    is_64: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], address: u64, is_64: bool) -> Self {
+        Reader {
+            bytes,
+            position: 0,
+            address,
+            is_64,
+        }
+    }
+
+    fn pointer(&mut self) -> Result<u64, Error> {
+        if self.is_64 {
+            self.u64()
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+
+    fn current_address(&self) -> u64 {
+        self.address + self.position as u64
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| {
+                malformed(format!(
+                    "Unexpected end of .eh_frame data at {:#X}",
+                    self.current_address()
+                ))
+            })?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn uleb128(&mut self) -> Result<u64, Error> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= u64::from(byte & 0x7F) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Result<i64, Error> {
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= i64::from(byte & 0x7F) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                //sign extend if the sign bit of the last byte is set
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1i64 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    fn cstr(&mut self) -> Result<&'a [u8], Error> {
+        let rest = &self.bytes[self.position.min(self.bytes.len())..];
+        let length = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| malformed("Unterminated augmentation string in .eh_frame"))?;
+        self.position += length + 1;
+        Ok(&rest[..length])
+    }
+
+    //read a pointer with a DW_EH_PE encoding, data_base is used for datarel values
+    fn encoded_pointer(&mut self, encoding: u8, data_base: u64) -> Result<u64, Error> {
+        let field_address = self.current_address();
+
+        let value = match encoding & 0x0F {
+            DW_EH_PE_ABSPTR => self.pointer()?,
+            DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => self.u64()?,
+            DW_EH_PE_ULEB128 => self.uleb128()?,
+            DW_EH_PE_UDATA2 => u64::from(self.u16()?),
+            DW_EH_PE_UDATA4 => u64::from(self.u32()?),
+            DW_EH_PE_SLEB128 => self.sleb128()? as u64,
+            DW_EH_PE_SDATA2 => self.u16()? as i16 as u64,
+            DW_EH_PE_SDATA4 => self.u32()? as i32 as u64,
+            format => {
+                return Err(malformed(format!(
+                    "Unsupported pointer encoding {:#X}",
+                    format
+                )))
+            }
+        };
+
+        let pointer = match encoding & 0x70 {
+            0 => value,
+            DW_EH_PE_PCREL => field_address.wrapping_add(value),
+            DW_EH_PE_DATAREL => data_base.wrapping_add(value),
+            application => {
+                return Err(malformed(format!(
+                    "Unsupported pointer application {:#X}",
+                    application
+                )))
+            }
+        };
+        //32-bit addresses wrap around at 4 GiB
+        if self.is_64 {
+            Ok(pointer)
+        } else {
+            Ok(pointer & 0xFFFF_FFFF)
+        }
+    }
+}
+
+//find the bytes of .eh_frame and its virtual address
+//the section header is used if there is one, otherwise PT_GNU_EH_FRAME points to .eh_frame_hdr
+//which holds a pointer to .eh_frame, that survives even if the section headers are stripped
+fn locate_eh_frame<'a>(elf: &Elf, buffer: &'a [u8]) -> Result<Option<(&'a [u8], u64)>, Error> {
+    for section in &elf.section_headers {
+        if elf.shdr_strtab.get_at(section.sh_name) == Some(".eh_frame") {
+            let bytes = file_range(buffer, section.sh_offset, section.sh_size)
+                .ok_or_else(|| malformed(".eh_frame section is outside of the file"))?;
+            return Ok(Some((bytes, section.sh_addr)));
+        }
+    }
+
+    let header = match elf
+        .program_headers
+        .iter()
+        .find(|header| header.p_type == PT_GNU_EH_FRAME)
+    {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let hdr_bytes = file_range(buffer, header.p_offset, header.p_filesz)
+        .ok_or_else(|| malformed(".eh_frame_hdr is outside of the file"))?;
+    let eh_frame_address = match eh_frame_pointer(hdr_bytes, header.p_vaddr, elf.is_64)? {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+
+    //without a section header the size is unknown, the terminator record ends the walk
+    //and the containing segment bounds it
+    let segment = elf
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .find(|header| {
+            eh_frame_address >= header.p_vaddr
+                && eh_frame_address - header.p_vaddr < header.p_filesz
+        })
+        .ok_or_else(|| malformed(".eh_frame pointer of .eh_frame_hdr is not mapped"))?;
+    let offset = eh_frame_address - segment.p_vaddr;
+    let bytes = file_range(buffer, segment.p_offset, segment.p_filesz)
+        .and_then(|segment_bytes| segment_bytes.get(offset as usize..))
+        .ok_or_else(|| malformed(".eh_frame is outside of the file"))?;
+
+    Ok(Some((bytes, eh_frame_address)))
+}
+
+//size bytes of the file at offset, None if they arent all in it, the header values can be anything
+fn file_range(buffer: &[u8], offset: u64, size: u64) -> Option<&[u8]> {
+    let start = usize::try_from(offset).ok()?;
+    let end = start.checked_add(usize::try_from(size).ok()?)?;
+    buffer.get(start..end)
+}
+
+//address of .eh_frame from the eh_frame_ptr of .eh_frame_hdr at address, None if it is omitted
+//.eh_frame_hdr: version, eh_frame_ptr encoding, fde_count encoding, table encoding, eh_frame_ptr
+fn eh_frame_pointer(hdr_bytes: &[u8], address: u64, is_64: bool) -> Result<Option<u64>, Error> {
+    let mut reader = Reader::new(hdr_bytes, address, is_64);
+    let version = reader.u8()?;
+    if version != 1 {
+        return Err(malformed(format!(
+            "Unsupported .eh_frame_hdr version {}",
+            version
+        )));
+    }
+    let eh_frame_ptr_encoding = reader.u8()?;
+    reader.u8()?;
+    reader.u8()?;
+    if eh_frame_ptr_encoding == DW_EH_PE_OMIT {
+        return Ok(None);
+    }
+    //datarel values are relative to the start of .eh_frame_hdr
+    reader
+        .encoded_pointer(eh_frame_ptr_encoding, address)
+        .map(Some)
+}
+
+//parse a CIE and return the pointer encoding its FDEs use for pc_begin and pc_range
+fn parse_cie(reader: &mut Reader) -> Result<u8, Error> {
+    let version = reader.u8()?;
+    let augmentation = reader.cstr()?;
+
+    //gcc 2.x eh_ptr, not used by anything this decade but it shifts the following fields
+    if augmentation.windows(2).any(|pair| pair == b"eh") {
+        reader.pointer()?;
+    }
+
+    //code alignment, data alignment, return address register
+    reader.uleb128()?;
+    reader.sleb128()?;
+    if version == 1 {
+        reader.u8()?;
+    } else {
+        reader.uleb128()?;
+    }
+
+    let mut fde_encoding = DW_EH_PE_ABSPTR;
+
+    if augmentation.first() == Some(&b'z') {
+        //augmentation data length
+        reader.uleb128()?;
+        for character in &augmentation[1..] {
+            match character {
+                //LSDA encoding
+                b'L' => {
+                    reader.u8()?;
+                }
+                //personality routine encoding and pointer
+                b'P' => {
+                    let encoding = reader.u8()?;
+                    reader.encoded_pointer(encoding, 0)?;
+                }
+                b'R' => fde_encoding = reader.u8()?,
+                b'S' | b'B' => {}
+                _ => break,
+            }
+        }
+    }
+
+    Ok(fde_encoding)
+}
+
+//walk every CIE/FDE record in .eh_frame and return the FDE ranges sorted by start address
+//an empty vec is returned if the binary has no .eh_frame
+pub fn parse_fde_ranges(elf: &Elf, buffer: &[u8]) -> Result<Vec<FdeRange>, Error> {
+    match locate_eh_frame(elf, buffer)? {
+        Some((bytes, eh_frame_address)) => parse_records(bytes, eh_frame_address, elf.is_64),
+        None => Ok(Vec::new()),
+    }
+}
+
+//read the length at the start of a record and return the end of the record, None for the zero
+//length terminator, the reader is left at the CIE id or CIE pointer
+//a length of 0xffffffff means the real length follows as 64-bit value
+fn read_record_length(reader: &mut Reader) -> Result<Option<usize>, Error> {
+    let record_start = reader.position;
+
+    let mut length = u64::from(reader.u32()?);
+    if length == 0 {
+        return Ok(None);
+    }
+    if length == 0xFFFF_FFFF {
+        length = reader.u64()?;
+    }
+    usize::try_from(length)
+        .ok()
+        .and_then(|length| reader.position.checked_add(length))
+        .filter(|end| *end <= reader.bytes.len())
+        .map(Some)
+        .ok_or_else(|| {
+            malformed(format!(
+                "Record at {:#X} runs past the end of .eh_frame",
+                reader.address.wrapping_add(record_start as u64)
+            ))
+        })
+}
+
+//FDE pointer encoding of the CIE at cie_start, for FDEs whose CIE wasnt seen while walking
+fn parse_cie_at(
+    bytes: &[u8],
+    cie_start: usize,
+    eh_frame_address: u64,
+    is_64: bool,
+) -> Result<u8, Error> {
+    let mut reader = Reader::new(bytes, eh_frame_address, is_64);
+    reader.position = cie_start;
+    if read_record_length(&mut reader)?.is_none() || reader.u32()? != 0 {
+        return Err(malformed(format!(
+            "FDE points to {:#X} which isnt a CIE",
+            eh_frame_address.wrapping_add(cie_start as u64)
+        )));
+    }
+    parse_cie(&mut reader)
+}
+
+//the FDE ranges of the records in bytes, the .eh_frame at eh_frame_address
+fn parse_records(bytes: &[u8], eh_frame_address: u64, is_64: bool) -> Result<Vec<FdeRange>, Error> {
+    let mut ranges = Vec::new();
+    //CIE offset in .eh_frame -> FDE pointer encoding
+    let mut cie_encodings: HashMap<usize, u8> = HashMap::new();
+
+    let mut reader = Reader::new(bytes, eh_frame_address, is_64);
+
+    while reader.position + 4 <= bytes.len() {
+        let record_start = reader.position;
+        let Some(record_end) = read_record_length(&mut reader)? else {
+            break;
+        };
+
+        let id_position = reader.position;
+        let id = reader.u32()? as usize;
+
+        if id == 0 {
+            let encoding = parse_cie(&mut reader)?;
+            cie_encodings.insert(record_start, encoding);
+        } else {
+            //the CIE pointer is relative to the position of the pointer itself
+            let cie_start = id_position
+                .checked_sub(id)
+                .ok_or_else(|| malformed("FDE points in front of .eh_frame"))?;
+            let encoding = match cie_encodings.get(&cie_start) {
+                Some(encoding) => *encoding,
+                None => {
+                    let encoding = parse_cie_at(bytes, cie_start, eh_frame_address, is_64)?;
+                    cie_encodings.insert(cie_start, encoding);
+                    encoding
+                }
+            };
+
+            let pc_begin = reader.encoded_pointer(encoding, 0)?;
+            //pc_range is a length, it uses the format but not the application of the encoding
+            let pc_range = reader.encoded_pointer(encoding & 0x0F, 0)?;
+
+            //FDEs of functions discarded by the linker are left with pc_begin 0
+            if pc_range != 0 && pc_begin != 0 {
+                ranges.push(FdeRange {
+                    start: pc_begin,
+                    end: pc_begin.wrapping_add(pc_range),
+                });
+            }
+        }
+
+        reader.position = record_end;
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    ranges.dedup();
+
+    Ok(ranges)
+}
+
+//the FDE range containing the address, ranges must be sorted by start address
+pub fn find_fde_range(ranges: &[FdeRange], address: u64) -> Option<&FdeRange> {
+    //index of the first range starting after the address, the candidate is the one before
+    let index = ranges.partition_point(|range| range.start <= address);
+    ranges[..index].last().filter(|range| address < range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //CIE with augmentation zR, code alignment 1, data alignment -8, return address register 16
+    //and the FDE pointer encoding, padded with DW_CFA_nop
+    fn cie(fde_encoding: u8) -> Vec<u8> {
+        let mut record = vec![16, 0, 0, 0, 0, 0, 0, 0, 1, b'z', b'R', 0, 1, 0x78, 16, 1];
+        record.extend_from_slice(&[fde_encoding, 0, 0, 0]);
+        record
+    }
+
+    //FDE at fde_offset of the CIE at offset 0 with 4 byte pc_begin and pc_range, no augmentation
+    //data
+    fn fde(fde_offset: usize, pc_begin: u32, pc_range: u32) -> Vec<u8> {
+        let mut record = vec![16, 0, 0, 0];
+        record.extend_from_slice(&(fde_offset as u32 + 4).to_le_bytes());
+        record.extend_from_slice(&pc_begin.to_le_bytes());
+        record.extend_from_slice(&pc_range.to_le_bytes());
+        record.extend_from_slice(&[0, 0, 0, 0]);
+        record
+    }
+
+    #[test]
+    fn reads_pcrel_fdes_until_the_terminator() {
+        //pcrel sdata4, pc_begin is relative to its field at 0x2000 + 28
+        let mut bytes = cie(DW_EH_PE_PCREL | DW_EH_PE_SDATA4);
+        bytes.extend(fde(bytes.len(), (0x1000i32 - 0x201C) as u32, 0x20));
+        //zero terminator, the garbage behind it isnt read
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(
+            parse_records(&bytes, 0x2000, true).unwrap(),
+            vec![FdeRange {
+                start: 0x1000,
+                end: 0x1020
+            }]
+        );
+    }
+
+    #[test]
+    fn reads_absolute_32_bit_fdes() {
+        let mut bytes = cie(DW_EH_PE_UDATA4);
+        bytes.extend(fde(bytes.len(), 0x8049000, 0x40));
+        //a discarded function
+        bytes.extend(fde(bytes.len(), 0, 0x10));
+
+        assert_eq!(
+            parse_records(&bytes, 0x804A000, false).unwrap(),
+            vec![FdeRange {
+                start: 0x8049000,
+                end: 0x8049040
+            }]
+        );
+    }
+
+    //the same CIE with the 64-bit extended length
+    fn cie_64(fde_encoding: u8) -> Vec<u8> {
+        let mut record = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        record.extend_from_slice(&16u64.to_le_bytes());
+        record.extend_from_slice(&cie(fde_encoding)[4..]);
+        record
+    }
+
+    #[test]
+    fn reads_cies_with_extended_length() {
+        let mut bytes = cie_64(DW_EH_PE_UDATA4);
+        bytes.extend(fde(bytes.len(), 0x401000, 0x30));
+        assert_eq!(
+            parse_records(&bytes, 0x402000, true).unwrap(),
+            vec![FdeRange {
+                start: 0x401000,
+                end: 0x401030
+            }]
+        );
+
+        //the CIE of an FDE that wasnt walked is read from its start as well
+        assert_eq!(
+            parse_cie_at(&bytes, 0, 0x402000, true).unwrap(),
+            DW_EH_PE_UDATA4
+        );
+        assert!(parse_cie_at(&bytes, 28, 0x402000, true).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_records() {
+        //64-bit length that overflows the position
+        let mut bytes = vec![0xFF, 0xFF, 0xFF, 0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            parse_records(&bytes, 0x2000, true),
+            Err(Error::EhFrame(_))
+        ));
+
+        //past the end of the section
+        let mut bytes = cie(DW_EH_PE_UDATA4);
+        bytes[0] = 0x40;
+        assert!(matches!(
+            parse_records(&bytes, 0x2000, true),
+            Err(Error::EhFrame(_))
+        ));
+    }
+
+    #[test]
+    fn reads_eh_frame_hdr_pointer() {
+        //pcrel, the field is at 0x3004
+        let mut hdr = vec![1, DW_EH_PE_PCREL | DW_EH_PE_SDATA4, 0x03, 0x3B];
+        hdr.extend_from_slice(&(0x2000i32 - 0x3004).to_le_bytes());
+        assert_eq!(eh_frame_pointer(&hdr, 0x3000, true).unwrap(), Some(0x2000));
+
+        //datarel, relative to the start of .eh_frame_hdr
+        hdr[1] = DW_EH_PE_DATAREL | DW_EH_PE_SDATA4;
+        hdr[4..].copy_from_slice(&(-0x1000i32).to_le_bytes());
+        assert_eq!(eh_frame_pointer(&hdr, 0x3000, true).unwrap(), Some(0x2000));
+
+        hdr[1] = DW_EH_PE_OMIT;
+        assert_eq!(eh_frame_pointer(&hdr, 0x3000, true).unwrap(), None);
+
+        hdr[0] = 2;
+        assert!(eh_frame_pointer(&hdr, 0x3000, true).is_err());
+    }
+}
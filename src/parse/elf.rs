@@ -0,0 +1,98 @@
+use goblin::elf::header::{EM_386, EM_X86_64, ET_DYN, ET_REL};
+use goblin::elf::program_header::{PF_X, PT_LOAD};
+use goblin::elf::Elf;
+
+use super::{demangle, eh_frame, object, symbols, Binary, Format, Section, Segment};
+use crate::error::Error;
+
+//read the loadable segments, sections, symbols and .eh_frame of an ELF file
+pub fn parse_elf(bytes: Vec<u8>) -> Result<Binary, Error> {
+    let elf = Elf::parse(&bytes)?;
+
+    //x32 binaries are ELF32 but run x86-64 code, so the machine decides and not the class
+    let bitness = match elf.header.e_machine {
+        EM_386 => 32,
+        EM_X86_64 => 64,
+        machine => return Err(Error::UnsupportedMachine(machine.into())),
+    };
+
+    log::trace!("elf: {:#?}", &elf);
+
+    //object files have no segments, their sections are placed first
+    if elf.header.e_type == ET_REL {
+        return object::parse_object(bytes, bitness);
+    }
+
+    let segments: Vec<Segment> = elf
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .map(|header| Segment {
+            virtual_address: header.p_vaddr,
+            memory_size: header.p_memsz,
+            file_offset: header.p_offset,
+            file_size: header.p_filesz,
+            executable: header.p_flags & PF_X != 0,
+        })
+        .collect();
+
+    let sections = elf
+        .section_headers
+        .iter()
+        .map(|header| Section {
+            name: elf
+                .shdr_strtab
+                .get_at(header.sh_name)
+                .unwrap_or("")
+                .to_string(),
+            address: header.sh_addr,
+            size: header.sh_size,
+            file_offset: header.sh_offset,
+        })
+        .collect();
+
+    let symbols = symbols::collect_symbols(&elf, &bytes, bitness);
+    let data_symbols = symbols::collect_data_symbols(&elf);
+    let fde_ranges = parse_fde_ranges_or_warn(&elf, &bytes);
+    //-fno-plt code calls through the GOT, call [__stack_chk_fail@got] doesnt return either
+    let mut no_return = symbols::find_no_return_functions(&symbols);
+    no_return.extend(symbols::find_no_return_functions(&data_symbols));
+    let entry = elf.entry;
+    let position_independent = elf.header.e_type == ET_DYN;
+    //the first mapping starts at the page of the lowest segment
+    let image_base = segments
+        .iter()
+        .map(|segment| segment.virtual_address)
+        .min()
+        .unwrap_or(0)
+        & !0xFFF;
+
+    Ok(Binary {
+        format: Format::Elf,
+        bytes,
+        bitness,
+        entry,
+        segments,
+        sections,
+        symbols,
+        data_symbols,
+        fde_ranges,
+        no_return,
+        names: demangle::NameOptions::default(),
+        position_independent,
+        relocatable: false,
+        image_base,
+        bias: 0,
+    })
+}
+
+//a broken .eh_frame shouldnt stop the cfg generation, the other bound sources still work
+fn parse_fde_ranges_or_warn(elf: &Elf, binary: &[u8]) -> Vec<eh_frame::FdeRange> {
+    match eh_frame::parse_fde_ranges(elf, binary) {
+        Ok(ranges) => ranges,
+        Err(err) => {
+            log::warn!("{}", err);
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//writers that turn a cfg into the file formats other tools read
+pub mod ascii;
+pub mod dot;
+pub mod gexf;
+pub mod graphml;
+pub mod html;
+pub mod json;
+pub mod layout;
+pub mod mermaid;
+
+//escape text for an xml attribute value or element content
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::{CfgBuilder, Function};
+    use crate::parse::Binary;
+
+    // 0x1000 mov rax,rcx
+    // 0x1003 xor eax,eax
+    // 0x1005 cmp eax,10
+    // 0x1008 jge 0x100f
+    // 0x100a add eax,1
+    // 0x100d jmp 0x1005
+    // 0x100f ret
+    const LOOP: &[u8] = &[
+        0x48, 0x89, 0xC8, 0x31, 0xC0, 0x83, 0xF8, 0x0A, 0x7D, 0x05, 0x83, 0xC0, 0x01, 0xEB, 0xF6,
+        0xC3,
+    ];
+
+    //64-bit raw code with a loop at 0x1000
+    pub fn loop_binary() -> Binary {
+        Binary::raw(LOOP.to_vec(), 64, 0x1000, 0).unwrap()
+    }
+
+    //the cfg of the loop, blocks 0x1000, 0x1005, 0x100a and 0x100f, 0x100a jumps back to 0x1005
+    pub fn loop_function(binary: &Binary) -> Function {
+        CfgBuilder::new(binary).build().unwrap()
+    }
+}
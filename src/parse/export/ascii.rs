@@ -0,0 +1,528 @@
+use super::layout::{layer_blocks, order_layers};
+use crate::cfg::Function;
+use crate::parse::graph::EdgeKind;
+use crate::syntax::AsmFormatter;
+
+//characters the boxes and edges are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    //plain ascii, works in every terminal
+    Ascii,
+    //box drawing characters
+    Unicode,
+}
+
+//longer instruction lines are cut so a single block doesnt make the graph unreadably wide
+const MAX_LINE_CHARS: usize = 60;
+//columns between two boxes of a layer
+const BOX_GAP: i64 = 3;
+
+//directions a line leaves a cell in
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+#[derive(Clone, Copy, Default)]
+struct Cell {
+    //directions of the lines through this cell
+    lines: u8,
+    //at least one of the lines is solid, otherwise they are all back edges and drawn dotted
+    solid: bool,
+    //text, arrow or label, drawn instead of the lines
+    text: Option<char>,
+}
+
+struct Canvas {
+    cells: Vec<Vec<Cell>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            cells: vec![vec![Cell::default(); width]; height],
+        }
+    }
+
+    fn connect(&mut self, x: usize, y: usize, directions: u8, solid: bool) {
+        let cell = &mut self.cells[y][x];
+        cell.lines |= directions;
+        cell.solid |= solid;
+    }
+
+    fn vertical(&mut self, x: usize, from: usize, to: usize, solid: bool) {
+        let (top, bottom) = (from.min(to), from.max(to));
+        for y in top..=bottom {
+            let mut directions = 0;
+            if y != top {
+                directions |= UP;
+            }
+            if y != bottom {
+                directions |= DOWN;
+            }
+            self.connect(x, y, directions, solid);
+        }
+    }
+
+    fn horizontal(&mut self, y: usize, from: usize, to: usize, solid: bool) {
+        let (left, right) = (from.min(to), from.max(to));
+        for x in left..=right {
+            let mut directions = 0;
+            if x != left {
+                directions |= LEFT;
+            }
+            if x != right {
+                directions |= RIGHT;
+            }
+            self.connect(x, y, directions, solid);
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (index, c) in text.chars().enumerate() {
+            self.cells[y][x + index].text = Some(c);
+        }
+    }
+
+    fn is_free(&self, x: usize, y: usize) -> bool {
+        self.cells
+            .get(y)
+            .and_then(|row| row.get(x))
+            .is_some_and(|cell| cell.lines == 0 && cell.text.is_none())
+    }
+
+    fn render(&self, charset: Charset) -> String {
+        let mut output = String::new();
+        for row in &self.cells {
+            let line: String = row
+                .iter()
+                .map(|cell| cell.text.unwrap_or_else(|| line_char(cell, charset)))
+                .collect();
+            output += line.trim_end();
+            output.push('\n');
+        }
+        output
+    }
+}
+
+fn line_char(cell: &Cell, charset: Charset) -> char {
+    let vertical = cell.lines & (UP | DOWN) != 0;
+    let horizontal = cell.lines & (LEFT | RIGHT) != 0;
+
+    match charset {
+        Charset::Ascii => match (vertical, horizontal, cell.solid) {
+            (false, false, _) => ' ',
+            (true, true, _) => '+',
+            (true, false, true) => '|',
+            (true, false, false) => ':',
+            (false, true, true) => '-',
+            (false, true, false) => '.',
+        },
+        Charset::Unicode => match cell.lines {
+            0 => ' ',
+            lines if lines == DOWN | RIGHT => '┌',
+            lines if lines == DOWN | LEFT => '┐',
+            lines if lines == UP | RIGHT => '└',
+            lines if lines == UP | LEFT => '┘',
+            lines if lines == UP | DOWN | RIGHT => '├',
+            lines if lines == UP | DOWN | LEFT => '┤',
+            lines if lines == DOWN | LEFT | RIGHT => '┬',
+            lines if lines == UP | LEFT | RIGHT => '┴',
+            lines if lines == UP | DOWN | LEFT | RIGHT => '┼',
+            _ if vertical && cell.solid => '│',
+            _ if vertical => '┆',
+            _ if cell.solid => '─',
+            _ => '┄',
+        },
+    }
+}
+
+//letter written next to an edge where it leaves its block, plain fallthroughs get none
+fn edge_letter(kind: EdgeKind) -> Option<char> {
+    match kind {
+        EdgeKind::Taken => Some('T'),
+        EdgeKind::NotTaken => Some('F'),
+        EdgeKind::Unconditional => Some('J'),
+        EdgeKind::Fallthrough | EdgeKind::CallReturn => None,
+    }
+}
+
+//a block or a dummy node, dummies carry long edges through the layers in between
+struct Node {
+    lines: Vec<String>,
+    //dummies are one column wide and have no box
+    dummy: bool,
+    layer: usize,
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+}
+
+impl Node {
+    fn center(&self) -> usize {
+        self.x + self.width / 2
+    }
+}
+
+//piece of an edge between two neighbouring layers, upper is always in the layer above lower
+struct Segment {
+    upper: usize,
+    lower: usize,
+    //back edges are drawn from the loop head down to the block that jumps back
+    back: bool,
+    kind: EdgeKind,
+    upper_column: usize,
+    lower_column: usize,
+}
+
+//cut a line to MAX_LINE_CHARS characters
+fn truncate(line: String) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        return line;
+    }
+    let mut cut: String = line.chars().take(MAX_LINE_CHARS - 3).collect();
+    cut += "...";
+    cut
+}
+
+//spread ports over the inside of a box, sorted by the column of the other end to avoid crossings
+fn assign_ports(node: &Node, ends: &mut [(usize, &mut usize)]) {
+    if node.dummy {
+        for (_, column) in ends.iter_mut() {
+            **column = node.x;
+        }
+        return;
+    }
+    ends.sort_by_key(|(other, _)| *other);
+    let inside = node.width - 2;
+    let count = ends.len();
+    for (index, (_, column)) in ends.iter_mut().enumerate() {
+        **column = node.x + 1 + ((index + 1) * inside / (count + 1)).min(inside - 1);
+    }
+}
+
+//draw the cfg as boxes and lines for a terminal, blocks are layered top to bottom from the entry
+//back edges are dotted and point up at the loop head, edges are lettered T taken, F not taken, J jmp
+pub fn create_ascii(function: &Function, formatter: &mut AsmFormatter, charset: Charset) -> String {
+    let graph = &function.cfg;
+    if graph.nodes.is_empty() {
+        return String::new();
+    }
+    let entry = graph
+        .nodes
+        .iter()
+        .position(|block| block.start_addr == function.address)
+        .unwrap_or(0);
+    let layering = layer_blocks(graph, entry);
+
+    //one node per block, node index == block id
+    let mut nodes: Vec<Node> = graph
+        .nodes
+        .iter()
+        .map(|block| {
+            let mut header = format!("block {}  0x{:X}", block.id, block.start_addr);
+            for edge in &graph.edges {
+                if edge.node_indexes == (block.id, block.id) {
+                    header += &format!("  (self loop: {})", edge.kind.label());
+                }
+            }
+            let mut lines = vec![header];
+            lines.extend(
+                block
+                    .format_assembly_line(formatter)
+                    .lines()
+                    .map(|line| truncate(line.to_string())),
+            );
+            let width = lines.iter().map(|line| line.chars().count()).max().unwrap() + 4;
+            let height = lines.len() + 2;
+            Node {
+                lines,
+                dummy: false,
+                layer: layering.layers[block.id as usize],
+                width,
+                height,
+                x: 0,
+                y: 0,
+            }
+        })
+        .collect();
+
+    //split every edge into segments between neighbouring layers, self loops are in the header
+    let mut segments = Vec::new();
+    for (edge, back) in graph.edges.iter().zip(&layering.back_edges) {
+        let (source, target) = (edge.node_indexes.0 as usize, edge.node_indexes.1 as usize);
+        if source == target {
+            continue;
+        }
+        let (upper, lower) = if *back {
+            (target, source)
+        } else {
+            (source, target)
+        };
+
+        let mut previous = upper;
+        for layer in nodes[upper].layer + 1..nodes[lower].layer {
+            nodes.push(Node {
+                lines: Vec::new(),
+                dummy: true,
+                layer,
+                width: 1,
+                height: 0,
+                x: 0,
+                y: 0,
+            });
+            let dummy = nodes.len() - 1;
+            segments.push(Segment {
+                upper: previous,
+                lower: dummy,
+                back: *back,
+                kind: edge.kind,
+                upper_column: 0,
+                lower_column: 0,
+            });
+            previous = dummy;
+        }
+        segments.push(Segment {
+            upper: previous,
+            lower,
+            back: *back,
+            kind: edge.kind,
+            upper_column: 0,
+            lower_column: 0,
+        });
+    }
+
+    //order the nodes inside their layers, blocks start in depth first order
+    let layer_count = nodes.iter().map(|node| node.layer).max().unwrap() + 1;
+    let mut order = vec![Vec::new(); layer_count];
+    for block in &layering.topological {
+        order[nodes[*block].layer].push(*block);
+    }
+    for (index, node) in nodes.iter().enumerate().skip(graph.nodes.len()) {
+        order[node.layer].push(index);
+    }
+    let mut neighbours = vec![Vec::new(); nodes.len()];
+    for segment in &segments {
+        neighbours[segment.upper].push(segment.lower);
+        neighbours[segment.lower].push(segment.upper);
+    }
+    order_layers(&mut order, &neighbours);
+
+    //columns: every node wants to be centered under the nodes above it, then it is pushed right
+    //until it doesnt overlap its left neighbour
+    let mut columns = vec![0i64; nodes.len()];
+    for layer in &order {
+        let mut cursor = i64::MIN;
+        for node in layer {
+            let above: Vec<i64> = segments
+                .iter()
+                .filter(|segment| segment.lower == *node)
+                .map(|segment| columns[segment.upper] + nodes[segment.upper].width as i64 / 2)
+                .collect();
+            let wanted = if above.is_empty() {
+                cursor.max(0)
+            } else {
+                above.iter().sum::<i64>() / above.len() as i64 - nodes[*node].width as i64 / 2
+            };
+            columns[*node] = wanted.max(cursor);
+            cursor = columns[*node] + nodes[*node].width as i64 + BOX_GAP;
+        }
+    }
+    let leftmost = *columns.iter().min().unwrap();
+    for (node, column) in nodes.iter_mut().zip(&columns) {
+        node.x = (column - leftmost) as usize;
+    }
+
+    for (index, node) in nodes.iter().enumerate() {
+        let mut bottom: Vec<(usize, &mut usize)> = Vec::new();
+        let mut top: Vec<(usize, &mut usize)> = Vec::new();
+        for segment in segments.iter_mut() {
+            if segment.upper == index {
+                bottom.push((nodes[segment.lower].center(), &mut segment.upper_column));
+            } else if segment.lower == index {
+                top.push((nodes[segment.upper].center(), &mut segment.lower_column));
+            }
+        }
+        assign_ports(node, &mut bottom);
+        assign_ports(node, &mut top);
+    }
+
+    //rows: every layer is as high as its highest box, below it is a channel with an arrow row
+    //on each side and one row for every edge that has to move sideways
+    let mut tracks = vec![Vec::new(); layer_count];
+    for (index, segment) in segments.iter().enumerate() {
+        if segment.upper_column != segment.lower_column {
+            tracks[nodes[segment.upper].layer].push(index);
+        }
+    }
+    //an edge that leaves at the column where another one arrives needs the higher track,
+    //otherwise both run down the same column
+    for layer_tracks in &mut tracks {
+        layer_tracks.sort_by_key(|index| segments[*index].upper_column);
+        let mut pending = std::mem::take(layer_tracks);
+        while !pending.is_empty() {
+            let next = pending
+                .iter()
+                .position(|later| {
+                    !pending.iter().any(|earlier| {
+                        earlier != later
+                            && segments[*earlier].upper_column == segments[*later].lower_column
+                    })
+                })
+                .unwrap_or(0);
+            layer_tracks.push(pending.remove(next));
+        }
+    }
+
+    let mut layer_tops = Vec::new();
+    let mut layer_heights = Vec::new();
+    let mut row = 0;
+    for (layer, nodes_of_layer) in order.iter().enumerate() {
+        let height = nodes_of_layer
+            .iter()
+            .map(|node| nodes[*node].height)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        layer_tops.push(row);
+        layer_heights.push(height);
+        row += height;
+        if layer + 1 < layer_count {
+            row += tracks[layer].len() + 2;
+        }
+    }
+    for node in nodes.iter_mut() {
+        node.y = layer_tops[node.layer];
+    }
+
+    let width = nodes.iter().map(|node| node.x + node.width).max().unwrap() + 1;
+    let mut canvas = Canvas::new(width, row);
+
+    for node in &nodes {
+        if node.dummy {
+            continue;
+        }
+        let (left, right) = (node.x, node.x + node.width - 1);
+        let (top, bottom) = (node.y, node.y + node.height - 1);
+        canvas.horizontal(top, left, right, true);
+        canvas.horizontal(bottom, left, right, true);
+        canvas.vertical(left, top, bottom, true);
+        canvas.vertical(right, top, bottom, true);
+        for (index, line) in node.lines.iter().enumerate() {
+            canvas.text(left + 2, top + 1 + index, line);
+        }
+    }
+
+    //lines first, arrows and letters go on top where there is room
+    let mut marks = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let upper = &nodes[segment.upper];
+        let lower = &nodes[segment.lower];
+        let solid = !segment.back;
+        let layer = upper.layer;
+        let channel_top = layer_tops[layer] + layer_heights[layer];
+
+        if upper.dummy {
+            canvas.vertical(upper.x, layer_tops[layer], channel_top - 1, solid);
+        }
+
+        //the edge starts at the bottom border of a box, right below it for an arrow into the
+        //loop head, or at the last row of a dummy
+        let mut start = if upper.dummy {
+            channel_top - 1
+        } else {
+            upper.y + upper.height - 1
+        };
+        if !upper.dummy && segment.back {
+            start += 1;
+            marks.push((segment.upper_column, start, arrow(charset, true), true));
+        }
+        if !upper.dummy && !segment.back {
+            if let Some(letter) = edge_letter(segment.kind) {
+                marks.push((segment.upper_column + 1, start + 1, letter, false));
+            }
+        }
+
+        //the edge ends at the top border of a box, above it with an arrow, or at a dummy
+        let mut end = lower.y;
+        if !lower.dummy && !segment.back {
+            end -= 1;
+            marks.push((segment.lower_column, end, arrow(charset, false), true));
+        }
+        if !lower.dummy && segment.back {
+            if let Some(letter) = edge_letter(segment.kind) {
+                marks.push((segment.lower_column + 1, end - 1, letter, false));
+            }
+        }
+
+        if segment.upper_column == segment.lower_column {
+            canvas.vertical(segment.upper_column, start, end, solid);
+        } else {
+            let track = tracks[layer]
+                .iter()
+                .position(|track| *track == index)
+                .unwrap();
+            let track_row = channel_top + 1 + track;
+            canvas.vertical(segment.upper_column, start, track_row, solid);
+            canvas.horizontal(track_row, segment.upper_column, segment.lower_column, solid);
+            canvas.vertical(segment.lower_column, track_row, end, solid);
+        }
+    }
+
+    //(column, row, character, drawn even if a line is there)
+    for (x, y, mark, always) in marks {
+        if always || canvas.is_free(x, y) {
+            canvas.cells[y][x].text = Some(mark);
+        }
+    }
+
+    let mut output = canvas.render(charset);
+    if !graph.edges.is_empty() {
+        output += "T taken, F not taken, J jmp, dotted edges go back to a loop head\n";
+    }
+    output
+}
+
+fn arrow(charset: Charset, up: bool) -> char {
+    match (charset, up) {
+        (Charset::Ascii, true) => '^',
+        (Charset::Ascii, false) => 'v',
+        (Charset::Unicode, true) => '▲',
+        (Charset::Unicode, false) => '▼',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::syntax::FormatOptions;
+
+    #[test]
+    fn draws_loops_as_dotted_back_edges() {
+        let binary = loop_binary();
+        let function = loop_function(&binary);
+
+        let layering = layer_blocks(&function.cfg, 0);
+        assert_eq!(layering.layers, vec![0, 1, 2, 2]);
+        assert_eq!(layering.back_edges, vec![false, false, false, true]);
+
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let ascii = create_ascii(&function, &mut formatter, Charset::Ascii);
+        let lines: Vec<&str> = ascii.lines().collect();
+        let row = |text: &str| lines.iter().position(|line| line.contains(text)).unwrap();
+
+        //every layer is drawn below the one before it, the loop body next to the exit
+        assert!(row("block 0") < row("block 1"));
+        assert!(row("block 1") < row("block 2"));
+        assert_eq!(row("block 2"), row("block 3"));
+        //the jmp back to the loop head is dotted and points up into block 1
+        let back_edge = &lines[row("block 1") + 3..row("block 2")];
+        assert!(back_edge.iter().any(|line| line.contains('^')));
+        assert!(back_edge.iter().any(|line| line.contains(':')));
+        assert!(back_edge.iter().any(|line| line.contains("....")));
+        assert!(back_edge.iter().any(|line| line.contains(":J")));
+        assert!(ascii.ends_with("dotted edges go back to a loop head\n"));
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use super::escape_xml;
+use crate::parse::graph::{BasicBlock, Graph};
+use crate::syntax::AsmFormatter;
+
+//how the instructions of a block are put into its node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DotLabels {
+    //one left justified line per instruction
+    #[default]
+    Text,
+    //html-like table with address, bytes, mnemonic and operands columns
+    Table,
+}
+
+//everything about the dot output that isnt the graph itself
+#[derive(Debug, Clone)]
+pub struct DotConfig {
+    pub graph_name: String,
+    pub labels: DotLabels,
+    //default attributes, written as graph [...], node [...] and edge [...]
+    pub graph_attributes: Vec<(String, String)>,
+    pub node_attributes: Vec<(String, String)>,
+    pub edge_attributes: Vec<(String, String)>,
+}
+
+impl Default for DotConfig {
+    //boxes in a monospace font, edges leave at the bottom and enter at the top
+    fn default() -> Self {
+        let attributes = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        DotConfig {
+            graph_name: "cfg".to_string(),
+            labels: DotLabels::Text,
+            graph_attributes: Vec::new(),
+            node_attributes: attributes(&[("shape", "box"), ("fontname", "monospace")]),
+            edge_attributes: attributes(&[("tailport", "s"), ("headport", "n")]),
+        }
+    }
+}
+
+//escape text for a double quoted dot string, a backslash would otherwise start an escape like \N
+pub fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_attributes(dot: &mut String, statement: &str, attributes: &[(String, String)]) {
+    if attributes.is_empty() {
+        return;
+    }
+    let attributes: Vec<String> = attributes
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_dot(value)))
+        .collect();
+    writeln!(dot, "    {} [{}]", statement, attributes.join(", ")).unwrap();
+}
+
+//every instruction on its own left justified line, \l ends a line and aligns it left
+fn text_label(block: &BasicBlock, formatter: &mut AsmFormatter) -> String {
+    let mut label = String::from("\"");
+    for (address, instruction) in &block.assembly_line {
+        let line = formatter.line(*address, instruction);
+        write!(label, "{}\\l", escape_dot(&line)).unwrap();
+    }
+    label.push('"');
+    label
+}
+
+//html-like label, a table row per instruction
+//the bytes column is always there, the table has room for it
+fn table_label(block: &BasicBlock, formatter: &mut AsmFormatter) -> String {
+    let mut label =
+        String::from("<<TABLE BORDER=\"0\" CELLBORDER=\"0\" CELLSPACING=\"0\" CELLPADDING=\"1\">");
+    for (address, instruction) in &block.assembly_line {
+        let bytes = formatter.hex_bytes(*address, instruction);
+        let mnemonic = formatter.mnemonic(instruction);
+        let operands = formatter.all_operands(instruction);
+
+        write!(
+            label,
+            "<TR><TD ALIGN=\"LEFT\">0x{:X}</TD><TD ALIGN=\"LEFT\"><FONT COLOR=\"gray40\">{}</FONT></TD>\
+             <TD ALIGN=\"LEFT\"><B>{}</B></TD><TD ALIGN=\"LEFT\">{}</TD></TR>",
+            address,
+            bytes,
+            escape_xml(&mnemonic),
+            escape_xml(&operands)
+        )
+        .unwrap();
+    }
+    label += "</TABLE>>";
+    label
+}
+
+//dot file of the cfg, nodes are named after the block ids and edges are colored and labeled
+//by their kind, the instructions are printed by the formatter
+pub fn create_dot(graph: &Graph, formatter: &mut AsmFormatter, config: &DotConfig) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph \"{}\" {{", escape_dot(&config.graph_name)).unwrap();
+    write_attributes(&mut dot, "graph", &config.graph_attributes);
+    write_attributes(&mut dot, "node", &config.node_attributes);
+    write_attributes(&mut dot, "edge", &config.edge_attributes);
+
+    for block in &graph.nodes {
+        let label = match config.labels {
+            DotLabels::Text => text_label(block, formatter),
+            DotLabels::Table => table_label(block, formatter),
+        };
+        writeln!(dot, "    {} [label={}]", block.id, label).unwrap();
+    }
+
+    for edge in &graph.edges {
+        writeln!(
+            dot,
+            "    {} -> {} [label=\"{}\", color=\"{}\", fontcolor=\"{}\"]",
+            edge.node_indexes.0,
+            edge.node_indexes.1,
+            edge.kind.label(),
+            edge.kind.color(),
+            edge.kind.color()
+        )
+        .unwrap();
+    }
+
+    dot += "}\n";
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::parse::symbols::Symbol;
+    use crate::syntax::FormatOptions;
+
+    //the loop with the target of jge named op{"<\>}, a data symbol doesnt end the function there
+    fn dot(labels: DotLabels) -> String {
+        let mut binary = loop_binary();
+        binary.data_symbols.push(Symbol {
+            name: "op{\"<\\>}".to_string(),
+            address: 0x100F,
+            size: 1,
+        });
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let config = DotConfig {
+            graph_name: "a \"quoted\" \\ name".to_string(),
+            labels,
+            ..DotConfig::default()
+        };
+        create_dot(&function.cfg, &mut formatter, &config)
+    }
+
+    #[test]
+    fn escapes_text_labels() {
+        let expected = r#"digraph "a \"quoted\" \\ name" {
+    node [shape="box", fontname="monospace"]
+    edge [tailport="s", headport="n"]
+    0 [label="0x1000 mov rax,rcx\l0x1003 xor eax,eax\l"]
+    1 [label="0x1005 cmp eax,0Ah\l0x1008 jge short op{\"<\\>}\l"]
+    2 [label="0x100A add eax,1\l0x100D jmp short 0000000000001005h\l"]
+    3 [label="0x100F ret\l"]
+    0 -> 1 [label="fallthrough", color="black", fontcolor="black"]
+    1 -> 2 [label="not taken", color="red", fontcolor="red"]
+    1 -> 3 [label="taken", color="green", fontcolor="green"]
+    2 -> 1 [label="jmp", color="blue", fontcolor="blue"]
+}
+"#;
+        assert_eq!(dot(DotLabels::Text), expected);
+    }
+
+    #[test]
+    fn escapes_table_labels() {
+        //html-like labels take xml entities, a backslash or brace is literal there
+        let expected = r#"    1 [label=<<TABLE BORDER="0" CELLBORDER="0" CELLSPACING="0" CELLPADDING="1"><TR><TD ALIGN="LEFT">0x1005</TD><TD ALIGN="LEFT"><FONT COLOR="gray40">83 F8 0A</FONT></TD><TD ALIGN="LEFT"><B>cmp</B></TD><TD ALIGN="LEFT">eax,0Ah</TD></TR><TR><TD ALIGN="LEFT">0x1008</TD><TD ALIGN="LEFT"><FONT COLOR="gray40">7D 05</FONT></TD><TD ALIGN="LEFT"><B>jge</B></TD><TD ALIGN="LEFT">short op{&quot;&lt;\&gt;}</TD></TR></TABLE>>]"#;
+        let dot = dot(DotLabels::Table);
+        assert_eq!(dot.lines().nth(4), Some(expected));
+        assert!(dot.starts_with("digraph \"a \\\"quoted\\\" \\\\ name\" {\n"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use super::escape_xml;
+use crate::parse::graph::Graph;
+use crate::syntax::AsmFormatter;
+
+//(id, title, gexf type) of the node attributes
+const NODE_ATTRIBUTES: [(u32, &str, &str); 6] = [
+    (0, "address", "long"),
+    (1, "size", "integer"),
+    (2, "instructions", "integer"),
+    (3, "in_degree", "integer"),
+    (4, "out_degree", "integer"),
+    (5, "code", "string"),
+];
+
+//gexf 1.3 document of the cfg, blocks are labeled with their start address
+//the code attribute of a block is its disassembly printed by the formatter
+pub fn create_gexf(graph: &Graph, formatter: &mut AsmFormatter) -> String {
+    let degrees = graph.degrees();
+
+    let mut xml = String::new();
+    xml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    xml += "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n";
+    xml += "  <graph mode=\"static\" defaultedgetype=\"directed\">\n";
+
+    xml += "    <attributes class=\"node\">\n";
+    for (id, title, value_type) in NODE_ATTRIBUTES {
+        writeln!(
+            xml,
+            "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>",
+            id, title, value_type
+        )
+        .unwrap();
+    }
+    xml += "    </attributes>\n";
+    xml += "    <attributes class=\"edge\">\n";
+    xml += "      <attribute id=\"0\" title=\"kind\" type=\"string\"/>\n";
+    xml += "    </attributes>\n";
+
+    xml += "    <nodes>\n";
+    for (block, (in_degree, out_degree)) in graph.nodes.iter().zip(degrees) {
+        writeln!(
+            xml,
+            "      <node id=\"{}\" label=\"0x{:X}\">",
+            block.id, block.start_addr
+        )
+        .unwrap();
+        xml += "        <attvalues>\n";
+        for (id, value) in [
+            block.start_addr.to_string(),
+            (block.byte_end() - block.start_addr).to_string(),
+            block.assembly_line.len().to_string(),
+            in_degree.to_string(),
+            out_degree.to_string(),
+            escape_xml(&block.format_assembly_line(formatter)),
+        ]
+        .iter()
+        .enumerate()
+        {
+            writeln!(
+                xml,
+                "          <attvalue for=\"{}\" value=\"{}\"/>",
+                id, value
+            )
+            .unwrap();
+        }
+        xml += "        </attvalues>\n";
+        xml += "      </node>\n";
+    }
+    xml += "    </nodes>\n";
+
+    xml += "    <edges>\n";
+    for (index, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            xml,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" label=\"{}\">",
+            index,
+            edge.node_indexes.0,
+            edge.node_indexes.1,
+            edge.kind.id()
+        )
+        .unwrap();
+        writeln!(
+            xml,
+            "        <attvalues>\n          <attvalue for=\"0\" value=\"{}\"/>\n        </attvalues>",
+            edge.kind.id()
+        )
+        .unwrap();
+        xml += "      </edge>\n";
+    }
+    xml += "    </edges>\n";
+
+    xml += "  </graph>\n";
+    xml += "</gexf>\n";
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::syntax::FormatOptions;
+
+    #[test]
+    fn writes_well_formed_gexf() {
+        let binary = loop_binary();
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let xml = create_gexf(&function.cfg, &mut formatter);
+        let document = roxmltree::Document::parse(&xml).unwrap();
+
+        let root = document.root_element();
+        assert_eq!(root.tag_name().namespace(), Some("http://gexf.net/1.3"));
+        assert_eq!(root.attribute("version"), Some("1.3"));
+
+        let node = document
+            .descendants()
+            .find(|node| node.has_tag_name("node") && node.attribute("id") == Some("1"))
+            .unwrap();
+        assert_eq!(node.attribute("label"), Some("0x1005"));
+        let values: Vec<(&str, &str)> = node
+            .descendants()
+            .filter(|node| node.has_tag_name("attvalue"))
+            .map(|value| {
+                (
+                    value.attribute("for").unwrap(),
+                    value.attribute("value").unwrap(),
+                )
+            })
+            .collect();
+        //in from the entry block and the loop, out to the loop and the exit
+        assert_eq!(
+            &values[..5],
+            [
+                ("0", "4101"),
+                ("1", "5"),
+                ("2", "2"),
+                ("3", "2"),
+                ("4", "2")
+            ]
+        );
+        assert!(values[5].1.starts_with("0x1005 cmp eax,0Ah\n"));
+
+        let edges: Vec<(&str, &str, &str)> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("edge"))
+            .map(|edge| {
+                (
+                    edge.attribute("source").unwrap(),
+                    edge.attribute("target").unwrap(),
+                    edge.attribute("label").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(edges[3], ("2", "1", "unconditional"));
+    }
+}
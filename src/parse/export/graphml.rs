@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use super::escape_xml;
+use crate::parse::graph::Graph;
+use crate::syntax::AsmFormatter;
+
+//(id, element the key is for, name, graphml type)
+const KEYS: [(&str, &str, &str, &str); 7] = [
+    ("d0", "node", "address", "long"),
+    ("d1", "node", "size", "int"),
+    ("d2", "node", "instructions", "int"),
+    ("d3", "node", "in_degree", "int"),
+    ("d4", "node", "out_degree", "int"),
+    ("d5", "node", "label", "string"),
+    ("d6", "edge", "kind", "string"),
+];
+
+//graphml document of the cfg, blocks are nodes named after their id
+//the label of a block is its disassembly printed by the formatter
+pub fn create_graphml(graph: &Graph, formatter: &mut AsmFormatter) -> String {
+    let degrees = graph.degrees();
+
+    let mut xml = String::new();
+    xml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    xml += "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" \
+            xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+            xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns \
+            http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n";
+    for (id, element, name, value_type) in KEYS {
+        writeln!(
+            xml,
+            "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+            id, element, name, value_type
+        )
+        .unwrap();
+    }
+    xml += "  <graph id=\"cfg\" edgedefault=\"directed\">\n";
+
+    for (block, (in_degree, out_degree)) in graph.nodes.iter().zip(degrees) {
+        writeln!(xml, "    <node id=\"n{}\">", block.id).unwrap();
+        for (key, value) in [
+            ("d0", block.start_addr.to_string()),
+            ("d1", (block.byte_end() - block.start_addr).to_string()),
+            ("d2", block.assembly_line.len().to_string()),
+            ("d3", in_degree.to_string()),
+            ("d4", out_degree.to_string()),
+            ("d5", escape_xml(&block.format_assembly_line(formatter))),
+        ] {
+            writeln!(xml, "      <data key=\"{}\">{}</data>", key, value).unwrap();
+        }
+        xml += "    </node>\n";
+    }
+
+    for (index, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            xml,
+            "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">",
+            index, edge.node_indexes.0, edge.node_indexes.1
+        )
+        .unwrap();
+        writeln!(xml, "      <data key=\"d6\">{}</data>", edge.kind.id()).unwrap();
+        xml += "    </edge>\n";
+    }
+
+    xml += "  </graph>\n";
+    xml += "</graphml>\n";
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::syntax::FormatOptions;
+
+    #[test]
+    fn writes_well_formed_graphml() {
+        let binary = loop_binary();
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let xml = create_graphml(&function.cfg, &mut formatter);
+        let document = roxmltree::Document::parse(&xml).unwrap();
+
+        let keys: Vec<(&str, &str)> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("key"))
+            .map(|key| {
+                (
+                    key.attribute("id").unwrap(),
+                    key.attribute("attr.name").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(keys.len(), KEYS.len());
+        assert!(keys.contains(&("d6", "kind")));
+
+        //the loop block: address, size, instructions, degrees and its disassembly
+        let node = document
+            .descendants()
+            .find(|node| node.attribute("id") == Some("n2"))
+            .unwrap();
+        let data: Vec<&str> = node
+            .children()
+            .filter(|child| child.has_tag_name("data"))
+            .map(|data| data.text().unwrap())
+            .collect();
+        assert_eq!(&data[..5], ["4106", "5", "2", "1", "1"]);
+        assert_eq!(
+            data[5],
+            "0x100A add eax,1\n0x100D jmp short 0000000000001005h\n"
+        );
+
+        let kinds: Vec<&str> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("edge"))
+            .map(|edge| edge.first_element_child().unwrap().text().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            ["fallthrough", "not_taken", "taken", "unconditional"]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::cfg::Function;
+use crate::syntax::AsmFormatter;
+
+use super::json::cfg_json_value;
+
+//page with the styles and the renderer, the cfg json replaces the placeholder
+const VIEWER_TEMPLATE: &str = include_str!("viewer.html");
+const DATA_PLACEHOLDER: &str = "__CFG_DATA__";
+
+//self contained html page that draws the cfg of function, it works offline
+//pan with the mouse, zoom with the wheel, click a block to highlight its successors and
+//predecessors, search an address or collapse blocks in the toolbar
+pub fn create_html(function: &Function, formatter: &mut AsmFormatter) -> String {
+    let data = serde_json::to_string(&cfg_json_value(function, formatter)).unwrap();
+    //a </script> in a symbol name or instruction would end the data block early
+    let data = data.replace("</", "<\\/");
+
+    VIEWER_TEMPLATE.replace(DATA_PLACEHOLDER, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::parse::symbols::Symbol;
+    use crate::syntax::FormatOptions;
+
+    #[test]
+    fn embeds_the_cfg_and_loads_nothing() {
+        let mut binary = loop_binary();
+        //jge targets it, as a data symbol it doesnt end the function there
+        //the name must not end the script block
+        binary.data_symbols.push(Symbol {
+            name: "exit</script><b>".to_string(),
+            address: 0x100F,
+            size: 1,
+        });
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let html = create_html(&function, &mut formatter);
+
+        assert!(!html.contains(DATA_PLACEHOLDER));
+        let start = html.find("type=\"application/json\">").unwrap() + 24;
+        let end = start + html[start..].find("</script>").unwrap();
+        let data: serde_json::Value = serde_json::from_str(&html[start..end]).unwrap();
+        assert_eq!(data, cfg_json_value(&function, &mut formatter));
+        assert_eq!(
+            data["blocks"][1]["instructions"][1]["text"],
+            "jge short exit</script><b>"
+        );
+
+        //no scripts, styles or fonts from elsewhere, the only url is the svg namespace
+        for external in ["https://", "<link", " src=", "@import", "fetch("] {
+            assert!(!html.contains(external), "{}", external);
+        }
+        assert_eq!(
+            html.matches("http://").count(),
+            html.matches("http://www.w3.org/2000/svg").count()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//json export of a cfg with the metadata of every instruction
+//
+//schema version 2:
+//{
+//  "schema_version": 2,
+//  "function": { "name", "address", "bounds": { "start", "end", "source" }, "escapes": [{ "address", "target" }] },
+//  "blocks": [{
+//    "id", "start_address", "end_address" (address of the last instruction),
+//    "byte_range": [start, end) of the bytes the block covers,
+//    "instructions": [{
+//      "address", "length", "bytes" (lowercase hex), "text", "mnemonic", "operands": [string],
+//      "flow_control", "branch_target" (null unless a direct branch or call)
+//    }]
+//  }],
+//  "edges": [{ "source", "target", "kind" }]
+//}
+//addresses are strings of uppercase hex digits with 0x in front, as numbers they lose precision
+//above 2^53 in parsers that read every number as a double like javascript, the viewer included
+//block ids are the indexes into blocks and are used by the edges
+//flow_control is one of next, unconditional_branch, indirect_branch, conditional_branch, return,
+//call, indirect_call, interrupt, xbegin_xabort_xend, exception
+//bounds source is one of symbol, ehframe, heuristic
+//kind is one of fallthrough, taken, not_taken, unconditional, call_return
+//fields are only added within a version, removing or changing one bumps it
+//version 1 had the addresses as numbers
+
+use std::fmt::Write;
+
+use iced_x86::{FlowControl, Instruction};
+use serde_json::{json, Value};
+
+use crate::cfg::Function;
+use crate::syntax::AsmFormatter;
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+fn address(value: u64) -> String {
+    format!("0x{:X}", value)
+}
+
+fn flow_control_id(flow_control: FlowControl) -> &'static str {
+    match flow_control {
+        FlowControl::Next => "next",
+        FlowControl::UnconditionalBranch => "unconditional_branch",
+        FlowControl::IndirectBranch => "indirect_branch",
+        FlowControl::ConditionalBranch => "conditional_branch",
+        FlowControl::Return => "return",
+        FlowControl::Call => "call",
+        FlowControl::IndirectCall => "indirect_call",
+        FlowControl::Interrupt => "interrupt",
+        FlowControl::XbeginXabortXend => "xbegin_xabort_xend",
+        FlowControl::Exception => "exception",
+    }
+}
+
+fn instruction_json(
+    formatter: &mut AsmFormatter,
+    instruction_address: u64,
+    instruction: &Instruction,
+) -> Value {
+    //always lowercase and without spaces, whatever the formatter options say
+    let mut bytes = String::new();
+    for byte in formatter.bytes(instruction_address, instruction) {
+        write!(bytes, "{:02x}", byte).unwrap();
+    }
+
+    let text = formatter.format(instruction);
+    let mnemonic = formatter.mnemonic(instruction);
+    let operands = formatter.operands(instruction);
+
+    let branch_target = match instruction.flow_control() {
+        FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch | FlowControl::Call => {
+            Some(address(instruction.near_branch_target()))
+        }
+        _ => None,
+    };
+
+    json!({
+        "address": address(instruction_address),
+        "length": instruction.len(),
+        "bytes": bytes,
+        "text": text,
+        "mnemonic": mnemonic,
+        "operands": operands,
+        "flow_control": flow_control_id(instruction.flow_control()),
+        "branch_target": branch_target,
+    })
+}
+
+//serialize the cfg of function, instruction texts are printed by the formatter
+pub fn create_cfg_json(function: &Function, formatter: &mut AsmFormatter) -> String {
+    serde_json::to_string_pretty(&cfg_json_value(function, formatter)).unwrap()
+}
+
+//the json document of create_cfg_json before it is printed
+pub fn cfg_json_value(function: &Function, formatter: &mut AsmFormatter) -> Value {
+    let blocks: Vec<_> = function
+        .cfg
+        .nodes
+        .iter()
+        .map(|block| {
+            let instructions: Vec<_> = block
+                .assembly_line
+                .iter()
+                .map(|(address, instruction)| instruction_json(formatter, *address, instruction))
+                .collect();
+
+            json!({
+                "id": block.id,
+                "start_address": address(block.start_addr),
+                "end_address": address(block.end_addr),
+                "byte_range": [address(block.start_addr), address(block.byte_end())],
+                "instructions": instructions,
+            })
+        })
+        .collect();
+
+    let edges: Vec<_> = function
+        .cfg
+        .edges
+        .iter()
+        .map(|edge| {
+            json!({
+                "source": edge.node_indexes.0,
+                "target": edge.node_indexes.1,
+                "kind": edge.kind.id(),
+            })
+        })
+        .collect();
+
+    let escapes: Vec<_> = function
+        .escapes
+        .iter()
+        .map(|(source, target)| json!({ "address": address(*source), "target": address(*target) }))
+        .collect();
+
+    json!({
+        "schema_version": SCHEMA_VERSION,
+        "function": {
+            "name": function.name,
+            "address": address(function.address),
+            "bounds": {
+                "start": address(function.bounds.start),
+                "end": address(function.bounds.end),
+                "source": function.bounds.source.as_str(),
+            },
+            "escapes": escapes,
+        },
+        "blocks": blocks,
+        "edges": edges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::syntax::FormatOptions;
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn pins_schema_version_2() {
+        let binary = loop_binary();
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let json: Value =
+            serde_json::from_str(&create_cfg_json(&function, &mut formatter)).unwrap();
+
+        assert_eq!(json["schema_version"], 2);
+        assert_eq!(
+            keys(&json),
+            ["blocks", "edges", "function", "schema_version"]
+        );
+        assert_eq!(
+            keys(&json["function"]),
+            ["address", "bounds", "escapes", "name"]
+        );
+        assert_eq!(json["function"]["bounds"]["source"], "heuristic");
+
+        let block = &json["blocks"][1];
+        assert_eq!(
+            keys(block),
+            [
+                "byte_range",
+                "end_address",
+                "id",
+                "instructions",
+                "start_address"
+            ]
+        );
+        assert_eq!(block["byte_range"], json!(["0x1005", "0x100A"]));
+        let jge = &block["instructions"][1];
+        assert_eq!(
+            keys(jge),
+            [
+                "address",
+                "branch_target",
+                "bytes",
+                "flow_control",
+                "length",
+                "mnemonic",
+                "operands",
+                "text"
+            ]
+        );
+        assert_eq!(jge["bytes"], "7d05");
+        assert_eq!(jge["flow_control"], "conditional_branch");
+        assert_eq!(jge["address"], "0x1008");
+        assert_eq!(jge["branch_target"], "0x100F");
+
+        let edges: Vec<(u64, u64, &str)> = json["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| {
+                (
+                    edge["source"].as_u64().unwrap(),
+                    edge["target"].as_u64().unwrap(),
+                    edge["kind"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, "fallthrough"),
+                (1, 2, "not_taken"),
+                (1, 3, "taken"),
+                (2, 1, "unconditional"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_addresses_above_2_pow_53_exact() {
+        let mut binary = loop_binary();
+        binary.rebase(0xFFFF_FFFF_FFFF_1001);
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let json = cfg_json_value(&function, &mut formatter);
+
+        assert_eq!(json["function"]["address"], "0xFFFFFFFFFFFF1001");
+        assert_eq!(json["function"]["bounds"]["end"], "0xFFFFFFFFFFFF1011");
+        assert_eq!(
+            json["blocks"][1]["instructions"][1]["branch_target"],
+            "0xFFFFFFFFFFFF1010"
+        );
+    }
+}
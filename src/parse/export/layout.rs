@@ -0,0 +1,139 @@
+use crate::parse::graph::Graph;
+
+//layered layout of a cfg, blocks flow top to bottom from the entry
+pub struct Layering {
+    //layer of every block indexed by block id, the entry is in layer 0
+    pub layers: Vec<usize>,
+    //indexed like graph.edges, true if the edge goes back up against the layering (loops)
+    pub back_edges: Vec<bool>,
+    //block ids in depth first order, the blocks of a layer start out in this order
+    pub topological: Vec<usize>,
+}
+
+//number of up and down sweeps that sort the blocks of a layer by their neighbours
+const ORDER_SWEEPS: usize = 4;
+
+//block ids in reverse postorder of a depth first search, starting at the entry and then at every
+//block the entry doesnt reach (landing pads), ids are indexes into graph.nodes
+fn reverse_postorder(graph: &Graph, entry: usize) -> Vec<usize> {
+    let mut successors = vec![Vec::new(); graph.nodes.len()];
+    for edge in &graph.edges {
+        successors[edge.node_indexes.0 as usize].push(edge.node_indexes.1 as usize);
+    }
+
+    let mut visited = vec![false; graph.nodes.len()];
+    let mut postorder = Vec::new();
+
+    let roots = std::iter::once(entry).chain(0..graph.nodes.len());
+    for root in roots {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        //(block, index of the next successor to look at)
+        let mut stack = vec![(root, 0)];
+        while let Some((block, next)) = stack.last_mut() {
+            if let Some(&successor) = successors[*block].get(*next) {
+                *next += 1;
+                if !visited[successor] {
+                    visited[successor] = true;
+                    stack.push((successor, 0));
+                }
+            } else {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+//entry is the index of the entry block in graph.nodes
+//an edge is a back edge if it doesnt go forward in the depth first order, every other edge goes
+//at least one layer down, so each block sits one layer below its deepest predecessor
+pub fn layer_blocks(graph: &Graph, entry: usize) -> Layering {
+    let topological = reverse_postorder(graph, entry);
+
+    let mut position = vec![0; graph.nodes.len()];
+    for (index, block) in topological.iter().enumerate() {
+        position[*block] = index;
+    }
+
+    let back_edges: Vec<bool> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            position[edge.node_indexes.1 as usize] <= position[edge.node_indexes.0 as usize]
+        })
+        .collect();
+
+    let mut layers = vec![0; graph.nodes.len()];
+    for block in &topological {
+        for (edge, back) in graph.edges.iter().zip(&back_edges) {
+            if !back && edge.node_indexes.0 as usize == *block {
+                let target = edge.node_indexes.1 as usize;
+                layers[target] = layers[target].max(layers[*block] + 1);
+            }
+        }
+    }
+
+    Layering {
+        layers,
+        back_edges,
+        topological,
+    }
+}
+
+//sort every layer by the average position of the neighbours in the other layers
+//nodes are abstract here so dummy nodes of long edges can take part, neighbours is indexed by node
+pub fn order_layers(order: &mut [Vec<usize>], neighbours: &[Vec<usize>]) {
+    let node_count = neighbours.len();
+    let mut position = vec![0.0; node_count];
+    let mut layer_of = vec![0; node_count];
+    for (layer, nodes) in order.iter().enumerate() {
+        for (index, node) in nodes.iter().enumerate() {
+            position[*node] = index as f64;
+            layer_of[*node] = layer;
+        }
+    }
+
+    for sweep in 0..ORDER_SWEEPS {
+        //downwards on even sweeps, upwards on odd ones
+        let layers: Vec<usize> = if sweep % 2 == 0 {
+            (0..order.len()).collect()
+        } else {
+            (0..order.len()).rev().collect()
+        };
+        for layer in layers {
+            let barycenters: Vec<f64> = order[layer]
+                .iter()
+                .map(|node| {
+                    let others: Vec<f64> = neighbours[*node]
+                        .iter()
+                        .filter(|other| layer_of[**other] != layer)
+                        .map(|other| position[*other])
+                        .collect();
+                    if others.is_empty() {
+                        position[*node]
+                    } else {
+                        others.iter().sum::<f64>() / others.len() as f64
+                    }
+                })
+                .collect();
+
+            let mut sorted: Vec<(f64, usize)> = barycenters
+                .into_iter()
+                .zip(order[layer].iter().copied())
+                .collect();
+            //stable, so ties keep their previous order
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            order[layer] = sorted.into_iter().map(|(_, node)| node).collect();
+            for (index, node) in order[layer].iter().enumerate() {
+                position[*node] = index as f64;
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt::Write;
+
+use super::layout::layer_blocks;
+use crate::cfg::Function;
+use crate::syntax::AsmFormatter;
+
+//quote a label for mermaid, the characters that end a label are written as entity codes
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+//mermaid flowchart of the cfg, paste it into a ```mermaid block of a markdown comment
+//back edges (loops) are dotted, edges are colored like in the dot output
+pub fn create_mermaid(function: &Function, formatter: &mut AsmFormatter) -> String {
+    let graph = &function.cfg;
+    let entry = graph
+        .nodes
+        .iter()
+        .position(|block| block.start_addr == function.address)
+        .unwrap_or(0);
+    let layering = layer_blocks(graph, entry);
+
+    let mut mermaid = String::new();
+    mermaid += "flowchart TD\n";
+    mermaid += "    classDef block font-family:monospace,text-align:left\n";
+    mermaid += "    classDef entry font-family:monospace,text-align:left,stroke-width:3px\n";
+
+    for block in &graph.nodes {
+        let lines: Vec<String> = block
+            .format_assembly_line(formatter)
+            .lines()
+            .map(escape_mermaid)
+            .collect();
+        writeln!(
+            mermaid,
+            "    b{}[\"{}\"]:::{}",
+            block.id,
+            lines.join("<br/>"),
+            if block.id as usize == entry {
+                "entry"
+            } else {
+                "block"
+            }
+        )
+        .unwrap();
+    }
+
+    for (edge, back) in graph.edges.iter().zip(&layering.back_edges) {
+        writeln!(
+            mermaid,
+            "    b{} {}|{}| b{}",
+            edge.node_indexes.0,
+            if *back { "-.->" } else { "-->" },
+            edge.kind.label(),
+            edge.node_indexes.1
+        )
+        .unwrap();
+    }
+
+    //edges are numbered in the order they were written
+    for (index, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            mermaid,
+            "    linkStyle {} stroke:{},color:{}",
+            index,
+            edge.kind.color(),
+            edge.kind.color()
+        )
+        .unwrap();
+    }
+
+    mermaid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::export::tests::{loop_binary, loop_function};
+    use crate::parse::symbols::Symbol;
+    use crate::syntax::FormatOptions;
+
+    #[test]
+    fn writes_ids_edges_and_escaped_labels() {
+        let mut binary = loop_binary();
+        //jge targets it, as a data symbol it doesnt end the function there
+        //every character of the name ends a mermaid label
+        binary.data_symbols.push(Symbol {
+            name: "done<\"#1\">".to_string(),
+            address: 0x100F,
+            size: 1,
+        });
+        let function = loop_function(&binary);
+        let mut formatter = AsmFormatter::new(&FormatOptions::default(), &binary);
+        let mermaid = create_mermaid(&function, &mut formatter);
+        let lines: Vec<&str> = mermaid.lines().collect();
+
+        assert_eq!(lines[0], "flowchart TD");
+        assert_eq!(
+            lines[3],
+            "    b0[\"0x1000 mov rax,rcx<br/>0x1003 xor eax,eax\"]:::entry"
+        );
+        assert_eq!(
+            lines[4],
+            "    b1[\"0x1005 cmp eax,0Ah<br/>0x1008 jge short done#lt;#quot;#35;1#quot;#gt;\"]:::block"
+        );
+        //the loop goes back up and is dotted
+        assert_eq!(
+            &lines[7..11],
+            [
+                "    b0 -->|fallthrough| b1",
+                "    b1 -->|not taken| b2",
+                "    b1 -->|taken| b3",
+                "    b2 -.->|jmp| b1",
+            ]
+        );
+        assert_eq!(lines[14], "    linkStyle 3 stroke:blue,color:blue");
+    }
+}
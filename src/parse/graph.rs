@@ -1,18 +1,90 @@
-use iced_x86::{FlowControl, Formatter, Instruction, NasmFormatter};
+use iced_x86::{FlowControl, Formatter, Instruction, NasmFormatter, OpKind, Register};
+
+use crate::syntax::AsmFormatter;
 use std::collections::HashSet;
+#[derive(Default)]
 pub struct BasicBlock {
     pub id: u64,
     pub assembly_line: Vec<(u64, Instruction)>,
     pub start_addr: u64,
     pub end_addr: u64,
-    pub jmp_addr: u64,
+    //target of the jmp or jcc that ends the block, objects and raw code can branch to 0
+    pub jmp_addr: Option<u64>,
 }
 
 pub struct Edge {
     //index only, not basic blocks, i could modify it to that later, but its fine like this
     pub node_indexes: (u64, u64),
+    pub kind: EdgeKind,
+}
+
+//how control gets from the source block to the target block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    //source block has no branch at its end and runs into the next block
+    Fallthrough,
+    //conditional branch, condition is true
+    Taken,
+    //conditional branch, condition is false, execution goes on after the jcc
+    NotTaken,
+    //jmp to a known target
+    Unconditional,
+    //block ends with a call, execution continues here once the callee returns
+    CallReturn,
+}
+
+impl EdgeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Taken => "taken",
+            EdgeKind::NotTaken => "not taken",
+            EdgeKind::Unconditional => "jmp",
+            EdgeKind::CallReturn => "call return",
+        }
+    }
+
+    //stable identifier used in the machine readable exports
+    pub fn id(&self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Taken => "taken",
+            EdgeKind::NotTaken => "not_taken",
+            EdgeKind::Unconditional => "unconditional",
+            EdgeKind::CallReturn => "call_return",
+        }
+    }
+
+    //graphviz color used for the edge
+    pub fn color(&self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "black",
+            EdgeKind::Taken => "green",
+            EdgeKind::NotTaken => "red",
+            EdgeKind::Unconditional => "blue",
+            EdgeKind::CallReturn => "gray",
+        }
+    }
+
+    //kind of the edge to the next block, based on the last instruction of the source block
+    pub fn from_fallthrough(last_instruction: &Instruction) -> EdgeKind {
+        match last_instruction.flow_control() {
+            FlowControl::ConditionalBranch => EdgeKind::NotTaken,
+            FlowControl::Call | FlowControl::IndirectCall => EdgeKind::CallReturn,
+            _ => EdgeKind::Fallthrough,
+        }
+    }
+
+    //kind of the edge to the branch target, based on the branch instruction
+    pub fn from_branch(branch_instruction: &Instruction) -> EdgeKind {
+        match branch_instruction.flow_control() {
+            FlowControl::ConditionalBranch => EdgeKind::Taken,
+            _ => EdgeKind::Unconditional,
+        }
+    }
 }
 
+#[derive(Default)]
 pub struct Graph {
     pub nodes: Vec<BasicBlock>,
     pub edges: Vec<Edge>,
@@ -25,6 +97,16 @@ impl Graph {
             edges: Vec::new(),
         }
     }
+
+    //number of (incoming, outgoing) edges of every block, indexed by block id
+    pub fn degrees(&self) -> Vec<(usize, usize)> {
+        let mut degrees = vec![(0, 0); self.nodes.len()];
+        for edge in &self.edges {
+            degrees[edge.node_indexes.0 as usize].1 += 1;
+            degrees[edge.node_indexes.1 as usize].0 += 1;
+        }
+        degrees
+    }
 }
 
 impl BasicBlock {
@@ -34,7 +116,7 @@ impl BasicBlock {
             assembly_line: Vec::new(),
             start_addr: 0,
             end_addr: 0,
-            jmp_addr: 0,
+            jmp_addr: None,
         }
     }
 
@@ -43,16 +125,20 @@ impl BasicBlock {
         self.assembly_line.push((address, instruction));
     }
 
-    //convert the addr and Instruction obj to a string to use it in the dot files label
-    // and additional formatting for better output
-    pub fn convert_assembly_line_to_string(&self) -> String {
+    //address right after the last instruction, [start_addr, byte_end) are the bytes of the block
+    pub fn byte_end(&self) -> u64 {
+        self.assembly_line
+            .last()
+            .map(|(address, instruction)| address + instruction.len() as u64)
+            .unwrap_or(self.start_addr)
+    }
+
+    //convert the addr and Instruction obj to a string to use it in the labels of the outputs,
+    //one line per instruction in the syntax and with the options of the formatter
+    pub fn format_assembly_line(&self, formatter: &mut AsmFormatter) -> String {
         let mut assembly_line_string = String::new();
-        let mut formatter = NasmFormatter::new();
         for (address, instruction) in &self.assembly_line {
-            let address_str = format!("0x{:0X}", address);
-            assembly_line_string += &address_str;
-            assembly_line_string += " ";
-            formatter.format(&instruction, &mut assembly_line_string);
+            assembly_line_string += &formatter.line(*address, instruction);
             assembly_line_string.push('\n');
         }
 
@@ -60,256 +146,112 @@ impl BasicBlock {
     }
 }
 
-/* pub fn split_to_basic_blocks(assembly_line: Vec<(u64, Instruction)>, mut graph: Graph) -> Graph {
+//no_return holds the addresses of functions that never return, a call to one ends its block
+pub fn split_to_basic_blocks(
+    assembly_line: Vec<(u64, Instruction)>,
+    mut graph: Graph,
+    no_return: &HashSet<u64>,
+) -> Graph {
     // save branch target address
-    let mut jmp_targets = HashSet::new();
+    let mut branch_target_leaders = HashSet::new();
+    // instructions right after a block terminator or after a gap in the decoded code
+    let mut after_branch_leaders = HashSet::new();
+
+    // start leader
+    let start_leader = match assembly_line.first() {
+        Some((address, _)) => *address,
+        None => return graph,
+    };
 
     //determine jmp targets and store them in the HashSet
-    for (address, instruction) in &assembly_line {
-        if instruction.flow_control() == FlowControl::UnconditionalBranch
-            || instruction.flow_control() == FlowControl::ConditionalBranch
-        {
-            jmp_targets.insert(instruction.near_branch_target());
+    for (i, (_, instruction)) in assembly_line.iter().enumerate() {
+        match instruction.flow_control() {
+            FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch => {
+                branch_target_leaders.insert(instruction.near_branch_target());
+                after_branch_leaders.insert(instruction.next_ip());
+            }
+            FlowControl::IndirectBranch | FlowControl::Return | FlowControl::Exception => {
+                after_branch_leaders.insert(instruction.next_ip());
+            }
+            //gap filling decodes the bytes after it, they arent reached from the call
+            FlowControl::Call | FlowControl::IndirectCall
+                if calls_no_return(instruction, no_return) =>
+            {
+                after_branch_leaders.insert(instruction.next_ip());
+            }
+            _ => {}
         }
-    }
 
-    //print out jmp targets for debug
-    for i in &jmp_targets {
-        println!("{}", i);
+        //recursive descent skips unreachable bytes, so the next decoded instruction
+        //doesnt have to follow this one, if it doesnt it starts a new block
+        if let Some((next_address, _)) = assembly_line.get(i + 1) {
+            if *next_address != instruction.next_ip() {
+                after_branch_leaders.insert(*next_address);
+            }
+        }
     }
 
+    log::trace!("branch target leaders: {:X?}", branch_target_leaders);
+    log::trace!("after branch leaders: {:X?}", after_branch_leaders);
+    log::trace!("start leader: {:X}", start_leader);
+
     //construct basic blocks
     let mut current_block = BasicBlock::new();
     let mut splitted_blocks = Vec::new();
     let mut counter = 0;
 
     for (address, instruction) in assembly_line {
-        match instruction.flow_control() {
-            FlowControl::Next
-            | FlowControl::Call
-            | FlowControl::IndirectCall
-            | FlowControl::Interrupt
-            | FlowControl::XbeginXabortXend => {
-                if current_block.start_addr == 0 {
-                    current_block.start_addr = address;
-                }
-
-                //if current instrs address is in the hashset, create new block bcs we split here
-                if jmp_targets.contains(&address) {
-                    current_block.id = counter;
-
-                    if current_block.end_addr == 0 {
-                        current_block.end_addr = address;
-                    }
-
-                    splitted_blocks.push(current_block);
-
-                    counter += 1;
-                    current_block = BasicBlock::new();
-
-                    current_block.start_addr = address;
-                }
-                current_block.end_addr = address;
-                current_block.add_to_assembly_line(address, instruction);
-            }
-            //if anything else then next create new block
-            //UnconditionalBranch,,ConditionalBranch
-            FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch => {
-                //save target addr as a member variable as well
-                current_block.jmp_addr = instruction.near_branch_target();
-                current_block.add_to_assembly_line(address, instruction);
-
-                //if jmp is the first element
-                if current_block.start_addr == 0 {
-                    current_block.start_addr = address;
-                }
-                current_block.end_addr = address;
-
-                //if current instrs address is in the hashset (jmp is targeted by a jmp), and the next instrs is not
-                //we check this because if theres an instr which is a target, and a jmp right after that
-                //we would split 2 times thus creating an empty block
-                if jmp_targets.contains(&instruction.near_branch_target())
-                    && !jmp_targets.contains(&instruction.next_ip())
-                {
-                    current_block.id = counter;
-                    if current_block.end_addr == 0 {
-                        current_block.end_addr = address;
-                    }
-                    splitted_blocks.push(current_block);
-
-                    counter += 1;
-                    current_block = BasicBlock::new();
-                }
-            }
-            //IndirectBranch,Return,Exception
-            _ => {
-                //anything else just push it, we only scanned the binary until ret, dont have to worry about anything else
-                current_block.add_to_assembly_line(address, instruction);
-            }
-        };
-    }
-
-    //add last block
-    if !current_block.assembly_line.is_empty() {
-        current_block.id = counter;
-        //set end address to the last instruction address
-        current_block.end_addr = current_block.assembly_line.last().unwrap().0;
-        splitted_blocks.push(current_block);
-    }
-
-    //debug print
-    let mut formatter = NasmFormatter::new();
-    let mut output = String::new();
+        //if current instrs address is a leader, close the current block bcs we split here
+        if (branch_target_leaders.contains(&address) || after_branch_leaders.contains(&address))
+            && !current_block.assembly_line.is_empty()
+        {
+            current_block.id = counter;
+            splitted_blocks.push(current_block);
 
-    for (index, block) in splitted_blocks.iter().enumerate() {
-        println!(
-            "Block {}: start_addr: {:#X}, end_addr: {:#X}, jmp_addr: {:#X}",
-            block.id, block.start_addr, block.end_addr, block.jmp_addr
-        );
-        println!("Assembly lines:");
-        for (addr, instr) in &block.assembly_line {
-            output.clear();
-            formatter.format(&instr, &mut output);
-            println!("Address: {:#X}, Instruction: {:?}", addr, &output);
+            counter += 1;
+            current_block = BasicBlock::new();
         }
-        println!();
-    }
-
-    graph.nodes = splitted_blocks;
-
-    graph
-} */
-
-pub fn split_to_basic_blocks(assembly_line: Vec<(u64, Instruction)>, mut graph: Graph) -> Graph {
-    // save branch target address
-    let mut branch_target_leaders = HashSet::new();
-    let mut after_branch_leaders = HashSet::new();
 
-    // start leader
-    let mut start_leader = assembly_line[0].0;
+        if current_block.assembly_line.is_empty() {
+            current_block.start_addr = address;
+        }
 
-    //determine jmp targets and store them in the HashSet
-    for (i, (address, instruction)) in assembly_line.iter().enumerate() {
-        if instruction.flow_control() == FlowControl::UnconditionalBranch
-            || instruction.flow_control() == FlowControl::ConditionalBranch
+        if let FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch =
+            instruction.flow_control()
         {
-            branch_target_leaders.insert(instruction.near_branch_target());
-            after_branch_leaders.insert(instruction.next_ip());
-
-            /* if assembly_line[i + 1].1.flow_control() != FlowControl::UnconditionalBranch
-                || assembly_line[i + 1].1.flow_control() != FlowControl::ConditionalBranch
-            {
-                after_branch_leaders.insert(instruction.next_ip());
-            } */
+            //save target addr as a member variable as well
+            current_block.jmp_addr = Some(instruction.near_branch_target());
         }
-    }
 
-    //print out jmp targets for debug
-    for i in &branch_target_leaders {
-        println!("{:0X}", i);
-    }
-    println!("---------------");
-    for i in &after_branch_leaders {
-        println!("{:0X}", i);
-    }
-    println!("---------------");
-    println!("{:0X}", start_leader);
-
-    //construct basic blocks
-    let mut current_block = BasicBlock::new();
-    let mut splitted_blocks = Vec::new();
-    let mut counter = 0;
-
-    for (address, instruction) in assembly_line {
-        match instruction.flow_control() {
-            FlowControl::Next
-            | FlowControl::Call
-            | FlowControl::IndirectCall
-            | FlowControl::Interrupt
-            | FlowControl::XbeginXabortXend => {
-                if current_block.start_addr == 0 {
-                    current_block.start_addr = address;
-                }
-
-                //if current instrs address is in the hashset, create new block bcs we split here
-                if branch_target_leaders.contains(&address) {
-                    current_block.id = counter;
-
-                    if current_block.end_addr == 0 {
-                        current_block.end_addr = address;
-                    }
-
-                    splitted_blocks.push(current_block);
-
-                    counter += 1;
-                    current_block = BasicBlock::new();
-
-                    current_block.start_addr = address;
-                }
-                current_block.end_addr = address;
-                current_block.add_to_assembly_line(address, instruction);
-            }
-            //if anything else then next create new block
-            //UnconditionalBranch,,ConditionalBranch
-            FlowControl::UnconditionalBranch | FlowControl::ConditionalBranch => {
-                //save target addr as a member variable as well
-                current_block.jmp_addr = instruction.near_branch_target();
-                current_block.add_to_assembly_line(address, instruction);
-
-                //if jmp is the first element
-                if current_block.start_addr == 0 {
-                    current_block.start_addr = address;
-                }
-                current_block.end_addr = address;
-
-                //if current instrs address is in the hashset (jmp is targeted by a jmp), and the next instrs is not
-                //we check this because if theres an instr which is a target, and a jmp right after that
-                //we would split 2 times thus creating an empty block
-                if branch_target_leaders.contains(&instruction.near_branch_target())
-                    && !branch_target_leaders.contains(&instruction.next_ip())
-                {
-                    current_block.id = counter;
-                    if current_block.end_addr == 0 {
-                        current_block.end_addr = address;
-                    }
-                    splitted_blocks.push(current_block);
-
-                    counter += 1;
-                    current_block = BasicBlock::new();
-                }
-            }
-            //IndirectBranch,Return,Exception
-            _ => {
-                //anything else just push it, we only scanned the binary until ret, dont have to worry about anything else
-                current_block.add_to_assembly_line(address, instruction);
-            }
-        };
+        current_block.end_addr = address;
+        current_block.add_to_assembly_line(address, instruction);
     }
 
     //add last block
     if !current_block.assembly_line.is_empty() {
         current_block.id = counter;
-        //set end address to the last instruction address
-        current_block.end_addr = current_block.assembly_line.last().unwrap().0;
         splitted_blocks.push(current_block);
     }
 
-    //debug print
-    let mut formatter = NasmFormatter::new();
-    let mut output = String::new();
-
-    for (index, block) in splitted_blocks.iter().enumerate() {
-        println!(
-            "Block {}: start_addr: {:#X}, end_addr: {:#X}, jmp_addr: {:#X}",
-            block.id, block.start_addr, block.end_addr, block.jmp_addr
-        );
-        println!("Assembly lines:");
-        for (addr, instr) in &block.assembly_line {
-            output.clear();
-            formatter.format(&instr, &mut output);
-            println!("Address: {:#X}, Instruction: {:?}", addr, &output);
+    //formatting every instruction is expensive, only do it when its logged
+    if log::log_enabled!(log::Level::Trace) {
+        let mut formatter = NasmFormatter::new();
+        let mut output = String::new();
+
+        for block in &splitted_blocks {
+            log::trace!(
+                "block {}: start_addr: {:#X}, end_addr: {:#X}, jmp_addr: {:#X?}",
+                block.id,
+                block.start_addr,
+                block.end_addr,
+                block.jmp_addr
+            );
+            for (addr, instr) in &block.assembly_line {
+                output.clear();
+                formatter.format(instr, &mut output);
+                log::trace!("    {:#X}: {}", addr, output);
+            }
         }
-        println!();
     }
 
     graph.nodes = splitted_blocks;
@@ -319,26 +261,49 @@ pub fn split_to_basic_blocks(assembly_line: Vec<(u64, Instruction)>, mut graph:
 
 //little monkey
 
-pub fn determine_edges(mut graph: Graph) -> Graph {
-    //create edges between consecutive blocks
-    for i in 0..graph.nodes.len() - 1 {
-        graph.edges.push(Edge {
-            node_indexes: (graph.nodes[i].id, graph.nodes[i + 1].id),
-        });
+//no_return holds the addresses of functions that never return, calls to them dont fall through
+pub fn determine_edges(mut graph: Graph, no_return: &HashSet<u64>) -> Graph {
+    //create edges between a block and the block right after its last instruction
+    for block in &graph.nodes {
+        let last_instruction = match block.assembly_line.last() {
+            Some((_, instruction)) => instruction,
+            None => continue,
+        };
+
+        if !falls_through(last_instruction, no_return) {
+            continue;
+        }
+
+        //recursive descent can leave gaps, so the next block is looked up by address instead of index
+        if let Some(next_block) = graph
+            .nodes
+            .iter()
+            .find(|other_block| other_block.start_addr == last_instruction.next_ip())
+        {
+            graph.edges.push(Edge {
+                node_indexes: (block.id, next_block.id),
+                kind: EdgeKind::from_fallthrough(last_instruction),
+            });
+        }
     }
 
     //create edges between jmp target blocks
     for block in &graph.nodes {
-        //jmp addr is set 0 if not cond or uncond jmps in split to blocks
-        if block.jmp_addr != 0 {
+        //jmp addr is only set for cond or uncond jmps in split to blocks
+        if let Some(jmp_addr) = block.jmp_addr {
             //iterate through the graph nodes and uses position to take a closure where we check a condition
             //and if thats true we create and push the edge into the graph
             //couldve done this with a for loop and if, but this is for practicing
             if let Some(target_block_index) = graph.nodes.iter().position(|other_block| {
-                block.jmp_addr >= other_block.start_addr && block.jmp_addr <= other_block.end_addr
+                jmp_addr >= other_block.start_addr && jmp_addr <= other_block.end_addr
             }) {
+                let kind = match block.assembly_line.last() {
+                    Some((_, instruction)) => EdgeKind::from_branch(instruction),
+                    None => EdgeKind::Unconditional,
+                };
                 let edge = Edge {
                     node_indexes: (block.id, graph.nodes[target_block_index].id),
+                    kind,
                 };
                 graph.edges.push(edge);
             }
@@ -347,21 +312,210 @@ pub fn determine_edges(mut graph: Graph) -> Graph {
     graph
 }
 
-pub fn print_graph(graph: &Graph) {
-    println!("Nodes:");
-    let mut assembly_line = String::new();
-    let mut formatter = NasmFormatter::new();
+//check if execution can continue with the instruction after the blocks last instruction
+pub fn falls_through(last_instruction: &Instruction, no_return: &HashSet<u64>) -> bool {
+    match last_instruction.flow_control() {
+        FlowControl::UnconditionalBranch
+        | FlowControl::IndirectBranch
+        | FlowControl::Return
+        | FlowControl::Exception => false,
+        FlowControl::Call | FlowControl::IndirectCall => {
+            !calls_no_return(last_instruction, no_return)
+        }
+        _ => true,
+    }
+}
+
+//address a call goes to, the target of a direct call or the slot an indirect call reads its
+//target from, like call [__imp_ExitProcess] through the import address table
+//None for calls through registers
+pub fn call_target(instruction: &Instruction) -> Option<u64> {
+    match instruction.flow_control() {
+        FlowControl::Call => Some(instruction.near_branch_target()),
+        FlowControl::IndirectCall if instruction.op0_kind() == OpKind::Memory => {
+            if instruction.is_ip_rel_memory_operand() {
+                Some(instruction.ip_rel_memory_address())
+            } else if instruction.memory_base() == Register::None
+                && instruction.memory_index() == Register::None
+                && !matches!(instruction.memory_segment(), Register::FS | Register::GS)
+            {
+                //i386 has no rip relative operands, its slots are absolute [address] operands
+                Some(instruction.memory_displacement64())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+//true if the instruction calls a function that never returns, directly or through its slot
+pub fn calls_no_return(instruction: &Instruction, no_return: &HashSet<u64>) -> bool {
+    call_target(instruction).is_some_and(|target| no_return.contains(&target))
+}
+
+//log the blocks and edges of a graph at debug level
+pub fn log_graph(graph: &Graph, formatter: &mut AsmFormatter) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
     for block in &graph.nodes {
-        println!(
-            "ID: {}, Start Address: {:X}, End Address: {:X}, Jump Address: {:X}",
-            block.id, block.start_addr, block.end_addr, block.jmp_addr
+        log::debug!(
+            "block {}: start {:X}, end {:X}, jump {:X?}\n{}",
+            block.id,
+            block.start_addr,
+            block.end_addr,
+            block.jmp_addr,
+            block.format_assembly_line(formatter)
         );
-        println!("Assembly Line:");
-        println!("{}", block.convert_assembly_line_to_string());
-        println!("-------------------------");
     }
-    println!("Edges:");
     for edge in &graph.edges {
-        println!("Edge: {:?}", edge.node_indexes);
+        log::debug!("edge {:?} {}", edge.node_indexes, edge.kind.label());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_x86::{Decoder, DecoderOptions};
+
+    //decode the whole buffer linearly, like reassemble would for straight line code
+    fn decode(bytes: &[u8], ip: u64) -> Vec<(u64, Instruction)> {
+        let mut decoder = Decoder::with_ip(64, bytes, ip, DecoderOptions::NONE);
+        let mut assembly_line = Vec::new();
+        while decoder.can_decode() {
+            let instruction = decoder.decode();
+            assembly_line.push((instruction.ip(), instruction));
+        }
+        assembly_line
+    }
+
+    fn edge_list(graph: &Graph) -> Vec<(u64, u64, EdgeKind)> {
+        let mut edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.node_indexes.0, edge.node_indexes.1, edge.kind))
+            .collect();
+        edges.sort_by_key(|edge| (edge.0, edge.1));
+        edges
+    }
+
+    // 0x1000 test edi,edi
+    // 0x1002 je 0x1008
+    // 0x1004 jmp 0x100d
+    // 0x1006 ud2
+    // 0x1008 call 0x2000
+    // 0x100d xor eax,eax
+    // 0x100f ret
+    const BYTES: &[u8] = &[
+        0x85, 0xFF, 0x74, 0x04, 0xEB, 0x07, 0x0F, 0x0B, 0xE8, 0xF3, 0x0F, 0x00, 0x00, 0x31, 0xC0,
+        0xC3,
+    ];
+
+    #[test]
+    fn no_fallthrough_after_jmp_ud2_and_no_return_call() {
+        let no_return = HashSet::from([0x2000]);
+        let graph = split_to_basic_blocks(decode(BYTES, 0x1000), Graph::new(), &no_return);
+        let graph = determine_edges(graph, &no_return);
+
+        let starts: Vec<u64> = graph.nodes.iter().map(|block| block.start_addr).collect();
+        assert_eq!(starts, vec![0x1000, 0x1004, 0x1006, 0x1008, 0x100D]);
+
+        assert_eq!(
+            edge_list(&graph),
+            vec![
+                (0, 1, EdgeKind::NotTaken),
+                (0, 3, EdgeKind::Taken),
+                (1, 4, EdgeKind::Unconditional),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_returns_into_next_block() {
+        let graph = split_to_basic_blocks(decode(BYTES, 0x1000), Graph::new(), &HashSet::new());
+        let graph = determine_edges(graph, &HashSet::new());
+
+        assert!(edge_list(&graph).contains(&(3, 4, EdgeKind::CallReturn)));
+        assert!(!edge_list(&graph).contains(&(2, 3, EdgeKind::Fallthrough)));
+    }
+
+    #[test]
+    fn no_return_call_ends_its_block() {
+        // 0x1000 call 0x2000
+        // 0x1005 mov eax,1
+        // 0x100a ret
+        let bytes = [0xE8, 0xFB, 0x0F, 0, 0, 0xB8, 0x01, 0, 0, 0, 0xC3];
+        let no_return = HashSet::from([0x2000]);
+        let graph = split_to_basic_blocks(decode(&bytes, 0x1000), Graph::new(), &no_return);
+        let graph = determine_edges(graph, &no_return);
+
+        let starts: Vec<u64> = graph.nodes.iter().map(|block| block.start_addr).collect();
+        assert_eq!(starts, vec![0x1000, 0x1005]);
+        assert!(graph.edges.is_empty());
+    }
+
+    #[test]
+    fn types_every_edge() {
+        // 0x1000 test edi,edi
+        // 0x1002 je 0x1009
+        // 0x1004 call 0x2000
+        // 0x1009 jmp 0x100d
+        // 0x100b inc eax, dead but decoded
+        // 0x100d ret
+        let bytes = [
+            0x85, 0xFF, 0x74, 0x05, 0xE8, 0xF7, 0x0F, 0, 0, 0xEB, 0x02, 0xFF, 0xC0, 0xC3,
+        ];
+        let graph = split_to_basic_blocks(decode(&bytes, 0x1000), Graph::new(), &HashSet::new());
+        let graph = determine_edges(graph, &HashSet::new());
+
+        assert_eq!(
+            edge_list(&graph),
+            vec![
+                (0, 1, EdgeKind::NotTaken),
+                (0, 2, EdgeKind::Taken),
+                (1, 2, EdgeKind::CallReturn),
+                (2, 4, EdgeKind::Unconditional),
+                (3, 4, EdgeKind::Fallthrough),
+            ]
+        );
+        let ids: Vec<&str> = edge_list(&graph)
+            .iter()
+            .map(|(_, _, kind)| kind.id())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "not_taken",
+                "taken",
+                "call_return",
+                "unconditional",
+                "fallthrough"
+            ]
+        );
+    }
+
+    #[test]
+    fn no_return_call_through_an_import_slot_ends_its_block() {
+        // 0x1000 call [rip+0xffa], the slot at 0x2000
+        // 0x1006 int3
+        let bytes = [0xFF, 0x15, 0xFA, 0x0F, 0, 0, 0xCC];
+        let no_return = HashSet::from([0x2000]);
+        let assembly_line = decode(&bytes, 0x1000);
+        assert_eq!(call_target(&assembly_line[0].1), Some(0x2000));
+        let graph = split_to_basic_blocks(assembly_line, Graph::new(), &no_return);
+        let graph = determine_edges(graph, &no_return);
+        assert_eq!(graph.nodes.len(), 2);
+        assert!(graph.edges.is_empty());
+
+        //i386 call [0x2000] and call eax
+        let mut decoder = Decoder::with_ip(
+            32,
+            &[0xFF, 0x15, 0, 0x20, 0, 0, 0xFF, 0xD0],
+            0x1000,
+            DecoderOptions::NONE,
+        );
+        assert_eq!(call_target(&decoder.decode()), Some(0x2000));
+        assert_eq!(call_target(&decoder.decode()), None);
     }
 }
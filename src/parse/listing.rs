@@ -0,0 +1,279 @@
+use std::fmt::Write;
+
+use serde_json::json;
+
+use super::{Binary, Format};
+use crate::cfg::Function;
+use crate::syntax::AsmFormatter;
+
+//symbol name at address, empty if there is none
+fn symbol_name(binary: &Binary, address: u64) -> String {
+    binary
+        .symbols
+        .iter()
+        .find(|symbol| symbol.address == address)
+        .map(|symbol| binary.display_name(&symbol.name))
+        .unwrap_or_default()
+}
+
+//end the line with the symbol name at address, lines of addresses without one end right there
+fn end_line_with_name(text: &mut String, binary: &Binary, address: u64) {
+    let name = symbol_name(binary, address);
+    if !name.is_empty() {
+        text.push(' ');
+        text.push_str(&name);
+    }
+    text.push('\n');
+}
+
+//one line per function described by an .eh_frame FDE, a .pdata entry or a Mach-O function start:
+//start, end, size and symbol name
+pub fn create_function_list_text(binary: &Binary) -> String {
+    let mut text = String::new();
+
+    for range in &binary.fde_ranges {
+        write!(
+            text,
+            "0x{:0width$X} 0x{:0width$X} {:>8}",
+            range.start,
+            range.end,
+            range.end - range.start,
+            width = binary.address_width()
+        )
+        .unwrap();
+        end_line_with_name(&mut text, binary, range.start);
+    }
+
+    text
+}
+
+//same as create_function_list_text, name is null if the function has no symbol
+pub fn create_function_list_json(binary: &Binary) -> String {
+    let functions: Vec<_> = binary
+        .fde_ranges
+        .iter()
+        .map(|range| {
+            let name = symbol_name(binary, range.start);
+            json!({
+                "start": range.start,
+                "end": range.end,
+                "size": range.end - range.start,
+                "name": if name.is_empty() { None } else { Some(name) },
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&functions).unwrap()
+}
+
+//PLT stubs of an ELF or Mach-O, import address table slots of a PE
+fn import_count(binary: &Binary) -> usize {
+    let stubs = binary
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.name.ends_with("@plt"))
+        .count();
+    let slots = binary
+        .data_symbols
+        .iter()
+        .filter(|symbol| symbol.name.starts_with("__imp_"))
+        .count();
+    stubs + slots
+}
+
+//overview of the binary: entry point, loadable segments, sections and what was found in them
+pub fn create_info_text(binary: &Binary) -> String {
+    let mut text = String::new();
+
+    writeln!(
+        text,
+        "format: {} {}-bit",
+        binary.format.name(),
+        binary.bitness
+    )
+    .unwrap();
+    write!(text, "entry: 0x{:X}", binary.entry).unwrap();
+    end_line_with_name(&mut text, binary, binary.entry);
+    writeln!(
+        text,
+        "type: {}",
+        match (binary.format, binary.position_independent) {
+            (Format::Raw, _) => "raw code",
+            (Format::Elf, _) if binary.relocatable => "relocatable object",
+            (Format::Elf, true) => "position independent (PIE or shared object)",
+            (Format::Pe, true) => "position independent (DLL or dynamic base)",
+            (Format::MachO, true) => "position independent (PIE or dylib)",
+            (_, false) => "executable",
+        }
+    )
+    .unwrap();
+    writeln!(text, "image base: 0x{:X}", binary.image_base).unwrap();
+    if binary.bias != 0 {
+        writeln!(text, "load bias: 0x{:X}", binary.bias).unwrap();
+    }
+    writeln!(text, "size: {} bytes", binary.bytes.len()).unwrap();
+    writeln!(
+        text,
+        "symbols: {} ({} imports)",
+        binary.symbols.len(),
+        import_count(binary)
+    )
+    .unwrap();
+    writeln!(text, "fdes: {}", binary.fde_ranges.len()).unwrap();
+    writeln!(text, "no-return functions: {}", binary.no_return.len()).unwrap();
+
+    writeln!(text, "segments:").unwrap();
+    for segment in &binary.segments {
+        writeln!(
+            text,
+            "  0x{:0width$X} {:>10} file offset 0x{:X} size {}{}",
+            segment.virtual_address,
+            segment.memory_size,
+            segment.file_offset,
+            segment.file_size,
+            if segment.executable {
+                " executable"
+            } else {
+                ""
+            },
+            width = binary.address_width()
+        )
+        .unwrap();
+    }
+
+    writeln!(text, "sections:").unwrap();
+    for section in binary
+        .sections
+        .iter()
+        .filter(|section| !section.name.is_empty())
+    {
+        writeln!(
+            text,
+            "  0x{:0width$X} {:>10} {}",
+            section.address,
+            section.size,
+            section.name,
+            width = binary.address_width()
+        )
+        .unwrap();
+    }
+
+    text
+}
+
+pub fn create_info_json(binary: &Binary) -> String {
+    let segments: Vec<_> = binary
+        .segments
+        .iter()
+        .map(|segment| {
+            json!({
+                "virtual_address": segment.virtual_address,
+                "memory_size": segment.memory_size,
+                "file_offset": segment.file_offset,
+                "file_size": segment.file_size,
+                "executable": segment.executable,
+            })
+        })
+        .collect();
+
+    let sections: Vec<_> = binary
+        .sections
+        .iter()
+        .filter(|section| !section.name.is_empty())
+        .map(|section| {
+            json!({
+                "name": section.name,
+                "address": section.address,
+                "size": section.size,
+                "file_offset": section.file_offset,
+            })
+        })
+        .collect();
+
+    let info = json!({
+        "format": binary.format.name(),
+        "bitness": binary.bitness,
+        "entry": binary.entry,
+        "position_independent": binary.position_independent,
+        "relocatable": binary.relocatable,
+        "image_base": binary.image_base,
+        "load_bias": binary.bias,
+        "size": binary.bytes.len(),
+        "symbols": binary.symbols.len(),
+        "imports": import_count(binary),
+        "fdes": binary.fde_ranges.len(),
+        "no_return_functions": binary.no_return.len(),
+        "segments": segments,
+        "sections": sections,
+    });
+
+    serde_json::to_string_pretty(&info).unwrap()
+}
+
+//listing of the decoded instructions of a function sorted by address, grouped by basic block
+pub fn create_disassembly(function: &Function, formatter: &mut AsmFormatter) -> String {
+    let mut text = String::new();
+    let options = *formatter.options();
+    let width = formatter.address_width();
+
+    writeln!(
+        text,
+        "; {} 0x{:X}..0x{:X} ({:?})",
+        function.name, function.bounds.start, function.bounds.end, function.bounds.source
+    )
+    .unwrap();
+
+    for block in &function.cfg.nodes {
+        writeln!(text, "\n; block {}", block.id).unwrap();
+        for (address, instruction) in &block.assembly_line {
+            if options.uppercase_hex {
+                write!(text, "0x{:0width$X}  ", address).unwrap();
+            } else {
+                write!(text, "0x{:0width$x}  ", address).unwrap();
+            }
+            if options.raw_bytes {
+                write!(text, "{:<24}  ", formatter.hex_bytes(*address, instruction)).unwrap();
+            }
+            text += &formatter.format(instruction);
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::eh_frame::FdeRange;
+    use crate::parse::symbols::Symbol;
+
+    #[test]
+    fn leaves_no_trailing_space_without_a_name() {
+        let mut binary = Binary::raw(vec![0xC3; 0x20], 64, 0x1000, 0).unwrap();
+        binary.fde_ranges = vec![
+            FdeRange {
+                start: 0x1000,
+                end: 0x1010,
+            },
+            FdeRange {
+                start: 0x1010,
+                end: 0x1020,
+            },
+        ];
+        binary.symbols.push(Symbol {
+            name: "main".to_string(),
+            address: 0x1010,
+            size: 0x10,
+        });
+
+        let info = create_info_text(&binary);
+        assert!(info.lines().all(|line| line == line.trim_end()));
+        assert!(info.contains("entry: 0x1000\n"));
+        assert_eq!(
+            create_function_list_text(&binary),
+            "0x0000000000001000 0x0000000000001010       16\n\
+             0x0000000000001010 0x0000000000001020       16 main\n"
+        );
+    }
+}
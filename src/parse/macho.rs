@@ -0,0 +1,477 @@
+use goblin::mach::constants::cputype::{CPU_TYPE_X86, CPU_TYPE_X86_64};
+use goblin::mach::constants::{
+    SECTION_TYPE, S_LAZY_SYMBOL_POINTERS, S_NON_LAZY_SYMBOL_POINTERS, S_SYMBOL_STUBS,
+    VM_PROT_EXECUTE,
+};
+use goblin::mach::header::{MH_BUNDLE, MH_DYLIB, MH_PIE};
+use goblin::mach::load_command::{
+    CommandVariant, SIZEOF_SECTION_32, SIZEOF_SECTION_64, SIZEOF_SEGMENT_COMMAND_32,
+    SIZEOF_SEGMENT_COMMAND_64,
+};
+use goblin::mach::symbols::N_SECT;
+use goblin::mach::{Mach, MachO, SingleArch};
+use iced_x86::{Decoder, DecoderOptions, Mnemonic};
+
+use super::{demangle, eh_frame, symbols, Binary, Format, Section, Segment};
+use crate::error::Error;
+
+//indirect symbol table entries of symbols that were made local or absolute, they have no name
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+//the parts of a section header goblin doesnt keep
+struct RawSection {
+    address: u64,
+    size: u64,
+    //section type, the low byte of the flags
+    kind: u32,
+    //index of the first entry in the indirect symbol table
+    first_indirect: u32,
+    //size of one stub, 0 for pointer sections
+    stub_size: u32,
+}
+
+//read the segments, sections, symbols, stubs and function starts of a Mach-O file
+//a fat (universal) binary is read from its x86_64 slice, or the i386 one if there is none
+pub fn parse_macho(bytes: Vec<u8>) -> Result<Binary, Error> {
+    let (slice_offset, macho) = match Mach::parse(&bytes)? {
+        Mach::Binary(macho) => (0, macho),
+        Mach::Fat(fat) => {
+            let arches = fat.arches()?;
+            let index = [CPU_TYPE_X86_64, CPU_TYPE_X86]
+                .iter()
+                .find_map(|cputype| arches.iter().position(|arch| arch.cputype == *cputype))
+                .ok_or_else(|| {
+                    Error::UnsupportedMachine(arches.first().map_or(0, |arch| arch.cputype))
+                })?;
+            match fat.get(index)? {
+                SingleArch::MachO(macho) => (arches[index].offset as u64, macho),
+                SingleArch::Archive(_) => {
+                    return Err(Error::Malformed(
+                        "the x86 slice of the fat binary is an archive".to_string(),
+                    ))
+                }
+            }
+        }
+    };
+    //file offsets in the load commands are relative to the slice
+    let slice = &bytes[slice_offset as usize..];
+
+    let bitness = match macho.header.cputype {
+        CPU_TYPE_X86 => 32,
+        CPU_TYPE_X86_64 => 64,
+        cputype => return Err(Error::UnsupportedMachine(cputype)),
+    };
+
+    log::trace!("macho: {:#?}", &macho);
+
+    let mut segments = Vec::new();
+    let mut sections = Vec::new();
+    for segment in &macho.segments {
+        //__PAGEZERO covers the low 4GB with no access, it would make every small number mapped
+        if segment.initprot == 0 && segment.filesize == 0 {
+            continue;
+        }
+        segments.push(Segment {
+            virtual_address: segment.vmaddr,
+            memory_size: segment.vmsize,
+            file_offset: slice_offset + segment.fileoff,
+            file_size: segment.filesize,
+            executable: segment.initprot & VM_PROT_EXECUTE != 0,
+        });
+        for (section, _) in segment.sections()? {
+            sections.push(Section {
+                //the way otool and the linker name them
+                name: format!(
+                    "{},{}",
+                    section.segname().unwrap_or(""),
+                    section.name().unwrap_or("")
+                ),
+                address: section.addr,
+                size: section.size,
+                file_offset: if section.offset == 0 {
+                    0
+                } else {
+                    slice_offset + section.offset as u64
+                },
+            });
+        }
+    }
+
+    //the first mapping is __TEXT, it starts with the mach header
+    let image_base = segments
+        .iter()
+        .map(|segment| segment.virtual_address)
+        .min()
+        .unwrap_or(0);
+
+    let mut fde_ranges = collect_function_starts(&macho, slice, image_base, &segments, &sections);
+    trim_padding(&mut fde_ranges, &bytes, &sections, bitness);
+    let (mut symbols, mut data_symbols) = collect_symbols(&macho, &segments, &fde_ranges);
+    collect_stubs(&macho, slice, bitness, &mut symbols, &mut data_symbols);
+    //code can call through the name@got pointers instead of the stubs
+    let mut no_return = symbols::find_no_return_functions(&symbols);
+    no_return.extend(symbols::find_no_return_functions(&data_symbols));
+
+    let entry = macho.entry;
+    let position_independent =
+        macho.header.flags & MH_PIE != 0 || matches!(macho.header.filetype, MH_DYLIB | MH_BUNDLE);
+
+    Ok(Binary {
+        format: Format::MachO,
+        bytes,
+        bitness,
+        entry,
+        segments,
+        sections,
+        symbols,
+        data_symbols,
+        fde_ranges,
+        no_return,
+        names: demangle::NameOptions::default(),
+        position_independent,
+        relocatable: false,
+        image_base,
+        bias: 0,
+    })
+}
+
+//C names get an underscore in front on Mach-O, without it they match the ELF names and the
+//c++ and rust names demangle
+fn strip_underscore(name: &str) -> &str {
+    name.strip_prefix('_').unwrap_or(name)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+//LC_FUNCTION_STARTS is a list of ULEB128 deltas starting at __TEXT, it has every function even in
+//stripped binaries
+//a function ends where the next one starts or at the end of its section, so it includes the
+//alignment padding after it until trim_padding cuts it off
+fn collect_function_starts(
+    macho: &MachO,
+    slice: &[u8],
+    image_base: u64,
+    segments: &[Segment],
+    sections: &[Section],
+) -> Vec<eh_frame::FdeRange> {
+    let Some(command) = macho
+        .load_commands
+        .iter()
+        .find_map(|command| match &command.command {
+            CommandVariant::FunctionStarts(command) => Some(command),
+            _ => None,
+        })
+    else {
+        return Vec::new();
+    };
+    let start = command.dataoff as usize;
+    let Some(data) = slice.get(start..start + command.datasize as usize) else {
+        log::warn!("LC_FUNCTION_STARTS points outside the file");
+        return Vec::new();
+    };
+
+    let mut starts = Vec::new();
+    let mut address = image_base;
+    let mut cursor = 0;
+    while cursor < data.len() {
+        let mut delta = 0u64;
+        let mut shift = 0;
+        while let Some(byte) = data.get(cursor) {
+            cursor += 1;
+            delta |= u64::from(byte & 0x7F).checked_shl(shift).unwrap_or(0);
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        //the list is terminated by a 0 delta and padded with zeros
+        if delta == 0 {
+            break;
+        }
+        address = address.wrapping_add(delta);
+        starts.push(address);
+    }
+    starts.sort_unstable();
+
+    let mut ranges = Vec::new();
+    for (index, start) in starts.iter().enumerate() {
+        let Some(section) = sections
+            .iter()
+            .find(|section| *start >= section.address && *start < section.address + section.size)
+        else {
+            continue;
+        };
+        if !segments
+            .iter()
+            .any(|segment| segment.executable && segment.contains(*start))
+        {
+            continue;
+        }
+        let section_end = section.address + section.size;
+        let end = starts
+            .get(index + 1)
+            .map_or(section_end, |next| (*next).min(section_end));
+        ranges.push(eh_frame::FdeRange { start: *start, end });
+    }
+    ranges
+}
+
+//the ranges are used as authoritative bounds and gap filling would decode the nops and int3s
+//between two functions, so end each range after its last instruction that isnt padding
+//a range that doesnt decode cleanly to its end (data in code, cut off instruction) is kept
+fn trim_padding(
+    ranges: &mut [eh_frame::FdeRange],
+    bytes: &[u8],
+    sections: &[Section],
+    bitness: u32,
+) {
+    for range in ranges {
+        let Some(section) = sections.iter().find(|section| {
+            section.file_offset != 0
+                && range.start >= section.address
+                && range.end <= section.address + section.size
+        }) else {
+            continue;
+        };
+        let offset = (section.file_offset + range.start - section.address) as usize;
+        let Some(code) = bytes.get(offset..offset + (range.end - range.start) as usize) else {
+            continue;
+        };
+        range.end = range.start + code_length(code, bitness, range.start) as u64;
+    }
+}
+
+//length of code without the padding at its end, all of it if it isnt only whole instructions
+fn code_length(code: &[u8], bitness: u32, ip: u64) -> usize {
+    let mut decoder = Decoder::with_ip(bitness, code, ip, DecoderOptions::NONE);
+    let mut length = 0;
+    while decoder.can_decode() {
+        let instruction = decoder.decode();
+        if instruction.is_invalid() {
+            return code.len();
+        }
+        if !matches!(instruction.mnemonic(), Mnemonic::Nop | Mnemonic::Int3) {
+            length = decoder.position();
+        }
+    }
+    //a function of only nops is still a function
+    if length == 0 {
+        code.len()
+    } else {
+        length
+    }
+}
+
+//defined LC_SYMTAB symbols, the ones in executable segments are functions and the rest data
+//nlist has no sizes, function starts give them for the functions
+fn collect_symbols(
+    macho: &MachO,
+    segments: &[Segment],
+    fde_ranges: &[eh_frame::FdeRange],
+) -> (Vec<symbols::Symbol>, Vec<symbols::Symbol>) {
+    let mut functions = Vec::new();
+    let mut data = Vec::new();
+
+    for (name, nlist) in macho.symbols().flatten() {
+        //debug entries and undefined, absolute or indirect symbols
+        if nlist.is_stab() || nlist.get_type() != N_SECT || name.is_empty() {
+            continue;
+        }
+        let address = nlist.n_value;
+        let name = strip_underscore(name).to_string();
+
+        let executable = segments
+            .iter()
+            .any(|segment| segment.executable && segment.contains(address));
+        if executable {
+            let size = fde_ranges
+                .iter()
+                .find(|range| range.start == address)
+                .map_or(0, |range| range.end - range.start);
+            functions.push(symbols::Symbol {
+                name,
+                address,
+                size,
+            });
+        } else {
+            data.push(symbols::Symbol {
+                name,
+                address,
+                size: 0,
+            });
+        }
+    }
+
+    (functions, data)
+}
+
+//name the stubs (__stubs) and the pointers (__got, __la_symbol_ptr) of the imported functions
+//through the indirect symbol table, stubs get a name@plt symbol and pointers a name@got one so
+//they are treated like the PLT stubs and GOT slots of an ELF
+fn collect_stubs(
+    macho: &MachO,
+    slice: &[u8],
+    bitness: u32,
+    functions: &mut Vec<symbols::Symbol>,
+    data: &mut Vec<symbols::Symbol>,
+) {
+    let (Some(symbol_table), Some(dysymtab)) = (
+        &macho.symbols,
+        macho
+            .load_commands
+            .iter()
+            .find_map(|command| match &command.command {
+                CommandVariant::Dysymtab(dysymtab) => Some(dysymtab),
+                _ => None,
+            }),
+    ) else {
+        return;
+    };
+
+    let pointer_size = u64::from(bitness / 8);
+    for section in raw_sections(macho, slice) {
+        let (entry_size, suffix, symbols) = match section.kind {
+            S_SYMBOL_STUBS if section.stub_size != 0 => {
+                (u64::from(section.stub_size), "plt", &mut *functions)
+            }
+            S_LAZY_SYMBOL_POINTERS | S_NON_LAZY_SYMBOL_POINTERS => {
+                (pointer_size, "got", &mut *data)
+            }
+            _ => continue,
+        };
+
+        for entry in 0..section.size / entry_size {
+            let indirect = section.first_indirect as u64 + entry;
+            if indirect >= u64::from(dysymtab.nindirectsyms) {
+                break;
+            }
+            let offset = dysymtab.indirectsymoff as usize + indirect as usize * 4;
+            let Some(index) = read_u32(slice, offset) else {
+                break;
+            };
+            if index & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 {
+                continue;
+            }
+            let Ok((name, _)) = symbol_table.get(index as usize) else {
+                continue;
+            };
+            symbols.push(symbols::Symbol {
+                name: format!("{}@{}", strip_underscore(name), suffix),
+                address: section.address + entry * entry_size,
+                size: entry_size,
+            });
+        }
+    }
+}
+
+//every section header with the reserved1 and reserved2 fields, read from the segment commands
+fn raw_sections(macho: &MachO, slice: &[u8]) -> Vec<RawSection> {
+    let mut sections = Vec::new();
+
+    for command in &macho.load_commands {
+        let (first, count, size, is_64) = match &command.command {
+            CommandVariant::Segment64(segment) => (
+                command.offset + SIZEOF_SEGMENT_COMMAND_64,
+                segment.nsects,
+                SIZEOF_SECTION_64,
+                true,
+            ),
+            CommandVariant::Segment32(segment) => (
+                command.offset + SIZEOF_SEGMENT_COMMAND_32,
+                segment.nsects,
+                SIZEOF_SECTION_32,
+                false,
+            ),
+            _ => continue,
+        };
+
+        for index in 0..count as usize {
+            //sectname and segname are 16 bytes each, then addr and size are pointer sized
+            let header = first + index * size;
+            let section = if is_64 {
+                (
+                    read_u64(slice, header + 32),
+                    read_u64(slice, header + 40),
+                    read_u32(slice, header + 64),
+                    read_u32(slice, header + 68),
+                    read_u32(slice, header + 72),
+                )
+            } else {
+                (
+                    read_u32(slice, header + 32).map(u64::from),
+                    read_u32(slice, header + 36).map(u64::from),
+                    read_u32(slice, header + 56),
+                    read_u32(slice, header + 60),
+                    read_u32(slice, header + 64),
+                )
+            };
+            if let (Some(address), Some(size), Some(flags), Some(reserved1), Some(reserved2)) =
+                section
+            {
+                sections.push(RawSection {
+                    address,
+                    size,
+                    kind: flags & SECTION_TYPE,
+                    first_indirect: reserved1,
+                    stub_size: reserved2,
+                });
+            }
+        }
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_padding_after_the_last_instruction() {
+        //push rbp, pop rbp, ret, then a multi byte nop and int3s up to the next function
+        let code = [
+            0x55, 0x5D, 0xC3, 0x0F, 0x1F, 0x40, 0x00, 0x66, 0x90, 0xCC, 0xCC, 0x90,
+        ];
+        assert_eq!(code_length(&code, 64, 0x1000), 3);
+        //nops inside a function are kept
+        assert_eq!(code_length(&[0x90, 0xC3, 0x90], 64, 0x1000), 2);
+        //only padding or not whole instructions, nothing is cut off
+        assert_eq!(code_length(&[0x90, 0xCC], 64, 0x1000), 2);
+        assert_eq!(code_length(&[0xC3, 0x90, 0x0F], 64, 0x1000), 3);
+    }
+
+    #[test]
+    fn trims_ranges_inside_their_section() {
+        let sections = [Section {
+            name: "__TEXT,__text".to_string(),
+            address: 0x1000,
+            size: 0x10,
+            file_offset: 0x200,
+        }];
+        let mut bytes = vec![0; 0x200];
+        bytes.extend_from_slice(&[0x55, 0x5D, 0xC3, 0x90, 0xC3, 0xCC, 0xCC, 0xCC]);
+        bytes.extend_from_slice(&[0x0F, 0x0B, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90]);
+        let mut ranges = [
+            eh_frame::FdeRange {
+                start: 0x1000,
+                end: 0x1008,
+            },
+            eh_frame::FdeRange {
+                start: 0x1008,
+                end: 0x1010,
+            },
+        ];
+        trim_padding(&mut ranges, &bytes, &sections, 64);
+        assert_eq!((ranges[0].start, ranges[0].end), (0x1000, 0x1005));
+        assert_eq!((ranges[1].start, ranges[1].end), (0x1008, 0x100A));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use goblin::elf::reloc::{
+    R_386_32, R_386_PC32, R_386_PLT32, R_X86_64_32, R_X86_64_32S, R_X86_64_64, R_X86_64_GOTPCREL,
+    R_X86_64_GOTPCRELX, R_X86_64_PC32, R_X86_64_PC64, R_X86_64_PLT32, R_X86_64_REX_GOTPCRELX,
+};
+use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHN_ABS, SHN_UNDEF, SHT_NOBITS};
+use goblin::elf::sym::{STT_FUNC, STT_GNU_IFUNC, STT_OBJECT};
+use goblin::elf::{Elf, Sym};
+
+use super::{demangle, symbols, Binary, Format, Section, Segment};
+use crate::error::Error;
+
+//read a relocatable object (ET_REL, a .o file), it has sections but no segments and every
+//address in it is relative to its section
+//the allocated sections are laid out one after the other from 0 the way a linker would, so the
+//first one is at the address objdump shows, and the relocations are applied to the code, calls
+//to functions of other objects go to an extern area behind the sections where every undefined
+//symbol gets a name@plt slot like an import
+pub fn parse_object(bytes: Vec<u8>, bitness: u32) -> Result<Binary, Error> {
+    let elf = Elf::parse(&bytes)?;
+
+    let bases = layout_sections(&elf);
+
+    let mut segments = Vec::new();
+    let mut sections = Vec::new();
+    for (header, base) in elf.section_headers.iter().zip(&bases) {
+        sections.push(Section {
+            name: elf
+                .shdr_strtab
+                .get_at(header.sh_name)
+                .unwrap_or("")
+                .to_string(),
+            address: base.unwrap_or(0),
+            size: header.sh_size,
+            file_offset: header.sh_offset,
+        });
+        if let Some(base) = base {
+            segments.push(Segment {
+                virtual_address: *base,
+                memory_size: header.sh_size,
+                file_offset: header.sh_offset,
+                file_size: if header.sh_type == SHT_NOBITS {
+                    0
+                } else {
+                    header.sh_size
+                },
+                executable: header.sh_flags & u64::from(SHF_EXECINSTR) != 0,
+            });
+        }
+    }
+
+    let extern_start = segments
+        .iter()
+        .map(|segment| segment.virtual_address + segment.memory_size)
+        .max()
+        .unwrap_or(0)
+        .next_multiple_of(16);
+    let slot_size = u64::from(bitness / 8);
+    let (externs, mut symbols) = place_externs(&elf, &bases, extern_start, slot_size);
+    if !externs.is_empty() {
+        //the slots have no bytes, nothing is decoded there
+        segments.push(Segment {
+            virtual_address: extern_start,
+            memory_size: externs.len() as u64 * slot_size,
+            file_offset: 0,
+            file_size: 0,
+            executable: false,
+        });
+    }
+
+    let mut patched = bytes.clone();
+    apply_relocations(&elf, &mut patched, bitness, &bases, &externs);
+
+    let mut data_symbols = Vec::new();
+    for (index, sym) in elf.syms.iter().enumerate() {
+        let function = sym.st_type() == STT_FUNC || sym.st_type() == STT_GNU_IFUNC;
+        if !(function || sym.st_type() == STT_OBJECT) || sym.st_shndx == SHN_UNDEF as usize {
+            continue;
+        }
+        let (Some(name), Some(address)) = (
+            elf.strtab
+                .get_at(sym.st_name)
+                .filter(|name| !name.is_empty()),
+            symbol_address(&sym, index, &bases, &externs),
+        ) else {
+            continue;
+        };
+        let symbol = symbols::Symbol {
+            name: name.to_string(),
+            address,
+            size: sym.st_size,
+        };
+        if function {
+            symbols.push(symbol);
+        } else {
+            data_symbols.push(symbol);
+        }
+    }
+    let no_return = symbols::find_no_return_functions(&symbols);
+
+    //objects have no entry point, start at the first code
+    let entry = segments
+        .iter()
+        .filter(|segment| segment.executable)
+        .map(|segment| segment.virtual_address)
+        .min()
+        .unwrap_or(0);
+
+    Ok(Binary {
+        format: Format::Elf,
+        bytes: patched,
+        bitness,
+        entry,
+        segments,
+        sections,
+        symbols,
+        data_symbols,
+        //the FDEs point at the sections through relocations as well, the function symbols of an
+        //object have sizes anyway
+        fde_ranges: Vec::new(),
+        no_return,
+        names: demangle::NameOptions::default(),
+        position_independent: false,
+        relocatable: true,
+        image_base: 0,
+        bias: 0,
+    })
+}
+
+//address of every allocated section indexed by section index, None for the ones that arent
+//loaded like .symtab or the debug info
+fn layout_sections(elf: &Elf) -> Vec<Option<u64>> {
+    let mut cursor = 0u64;
+    elf.section_headers
+        .iter()
+        .map(|header| {
+            if header.sh_flags & u64::from(SHF_ALLOC) == 0 {
+                return None;
+            }
+            let base = cursor.next_multiple_of(header.sh_addralign.max(1));
+            cursor = base + header.sh_size;
+            Some(base)
+        })
+        .collect()
+}
+
+//one slot in the extern area per undefined symbol a relocation refers to, keyed by symbol index
+//the returned symbols are the name@plt symbols of the slots
+fn place_externs(
+    elf: &Elf,
+    bases: &[Option<u64>],
+    extern_start: u64,
+    slot_size: u64,
+) -> (HashMap<usize, u64>, Vec<symbols::Symbol>) {
+    let mut externs = HashMap::new();
+    let mut symbols = Vec::new();
+
+    for (reloc_section, relocs) in &elf.shdr_relocs {
+        if !relocates_loaded_section(elf, bases, *reloc_section) {
+            continue;
+        }
+        for reloc in relocs.iter() {
+            let Some(sym) = elf.syms.get(reloc.r_sym) else {
+                continue;
+            };
+            if sym.st_shndx != SHN_UNDEF as usize || externs.contains_key(&reloc.r_sym) {
+                continue;
+            }
+            let Some(name) = elf
+                .strtab
+                .get_at(sym.st_name)
+                .filter(|name| !name.is_empty())
+            else {
+                continue;
+            };
+            let address = extern_start + externs.len() as u64 * slot_size;
+            externs.insert(reloc.r_sym, address);
+            symbols.push(symbols::Symbol {
+                name: format!("{}@plt", name),
+                address,
+                size: slot_size,
+            });
+        }
+    }
+
+    (externs, symbols)
+}
+
+//true if the SHT_REL/SHT_RELA section at index applies to a section that is laid out
+fn relocates_loaded_section(elf: &Elf, bases: &[Option<u64>], reloc_section: usize) -> bool {
+    elf.section_headers
+        .get(reloc_section)
+        .and_then(|header| bases.get(header.sh_info as usize))
+        .is_some_and(|base| base.is_some())
+}
+
+//address of a symbol after the layout, None for common symbols and the ones without a slot
+fn symbol_address(
+    sym: &Sym,
+    index: usize,
+    bases: &[Option<u64>],
+    externs: &HashMap<usize, u64>,
+) -> Option<u64> {
+    match sym.st_shndx as u32 {
+        SHN_UNDEF => externs.get(&index).copied(),
+        SHN_ABS => Some(sym.st_value),
+        shndx => bases
+            .get(shndx as usize)
+            .copied()
+            .flatten()
+            .map(|base| base + sym.st_value),
+    }
+}
+
+//write the relocated values into the code and data so branch targets and memory operands point
+//at their symbols, only the types compilers use for them are applied, the rest stays 0
+//there is no GOT, the GOT relative ones point at the symbol itself which is where its GOT slot
+//would point to
+fn apply_relocations(
+    elf: &Elf,
+    bytes: &mut [u8],
+    bitness: u32,
+    bases: &[Option<u64>],
+    externs: &HashMap<usize, u64>,
+) {
+    for (reloc_section, relocs) in &elf.shdr_relocs {
+        if !relocates_loaded_section(elf, bases, *reloc_section) {
+            continue;
+        }
+        let target = &elf.section_headers[elf.section_headers[*reloc_section].sh_info as usize];
+        if target.sh_type == SHT_NOBITS {
+            continue;
+        }
+        let base = bases[elf.section_headers[*reloc_section].sh_info as usize].unwrap_or(0);
+
+        for reloc in relocs.iter() {
+            //(size in bytes, relative to the place)
+            let (size, pc_relative) = match (bitness, reloc.r_type) {
+                (64, R_X86_64_64) => (8, false),
+                (64, R_X86_64_PC64) => (8, true),
+                (64, R_X86_64_32 | R_X86_64_32S) => (4, false),
+                (
+                    64,
+                    R_X86_64_PC32
+                    | R_X86_64_PLT32
+                    | R_X86_64_GOTPCREL
+                    | R_X86_64_GOTPCRELX
+                    | R_X86_64_REX_GOTPCRELX,
+                ) => (4, true),
+                (32, R_386_32) => (4, false),
+                (32, R_386_PC32 | R_386_PLT32) => (4, true),
+                _ => continue,
+            };
+            let Some(symbol) = elf
+                .syms
+                .get(reloc.r_sym)
+                .and_then(|sym| symbol_address(&sym, reloc.r_sym, bases, externs))
+            else {
+                continue;
+            };
+
+            //r_offset comes from the file, a corrupt one can be anywhere
+            let field = target
+                .sh_offset
+                .checked_add(reloc.r_offset)
+                .and_then(|offset| usize::try_from(offset).ok())
+                .and_then(|offset| bytes.get_mut(offset..offset.checked_add(size)?));
+            let Some(field) = field else {
+                log::warn!(
+                    "relocation at {:#X} is outside the file, skipping it",
+                    reloc.r_offset
+                );
+                continue;
+            };
+            //x86-64 has the addend in the relocation, i386 in the field it relocates
+            let addend = match reloc.r_addend {
+                Some(addend) => addend as u64,
+                None => i32::from_le_bytes(field[..4].try_into().unwrap()) as u64,
+            };
+            let mut value = symbol.wrapping_add(addend);
+            if pc_relative {
+                value = value.wrapping_sub(base.wrapping_add(reloc.r_offset));
+            }
+            field.copy_from_slice(&value.to_le_bytes()[..size]);
+        }
+    }
+}
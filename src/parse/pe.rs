@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use goblin::pe::dll_characteristic::IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE;
+use goblin::pe::header::{COFF_MACHINE_X86, COFF_MACHINE_X86_64};
+use goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE;
+use goblin::pe::PE;
+
+use super::{demangle, eh_frame, symbols, Binary, Format, Section, Segment};
+use crate::error::Error;
+
+//read the sections, exports, imports and .pdata of a PE file
+//every address is image_base + RVA, the addresses the binary has when it isnt relocated
+pub fn parse_pe(bytes: Vec<u8>) -> Result<Binary, Error> {
+    let pe = PE::parse(&bytes)?;
+
+    let bitness = match pe.header.coff_header.machine {
+        COFF_MACHINE_X86 => 32,
+        COFF_MACHINE_X86_64 => 64,
+        machine => return Err(Error::UnsupportedMachine(machine.into())),
+    };
+
+    log::trace!("pe: {:#?}", &pe);
+
+    let image_base = pe.image_base as u64;
+    let windows_fields = pe
+        .header
+        .optional_header
+        .map(|header| header.windows_fields);
+
+    //the headers are mapped at image_base, the sections follow at their RVAs
+    let headers_size = windows_fields.map_or(0, |fields| fields.size_of_headers as u64);
+    let mut segments = vec![Segment {
+        virtual_address: image_base,
+        memory_size: headers_size,
+        file_offset: 0,
+        file_size: headers_size.min(bytes.len() as u64),
+        executable: false,
+    }];
+    let mut sections = Vec::new();
+    for section in &pe.sections {
+        //the raw data is padded to the file alignment, the virtual size is the real one
+        //linkers that dont set it leave it at 0
+        let memory_size = match section.virtual_size {
+            0 => section.size_of_raw_data as u64,
+            size => size as u64,
+        };
+        let address = image_base + section.virtual_address as u64;
+        segments.push(Segment {
+            virtual_address: address,
+            memory_size,
+            file_offset: section.pointer_to_raw_data as u64,
+            //uninitialized data has no raw data
+            file_size: (section.size_of_raw_data as u64).min(memory_size),
+            executable: section.characteristics & IMAGE_SCN_MEM_EXECUTE != 0,
+        });
+        sections.push(Section {
+            name: section.name().unwrap_or("").to_string(),
+            address,
+            size: memory_size,
+            file_offset: section.pointer_to_raw_data as u64,
+        });
+    }
+
+    let fde_ranges = collect_runtime_functions(&pe, image_base);
+    let (symbols, data_symbols) = collect_exports(&pe, image_base, &segments, &fde_ranges);
+    let imports = collect_imports(&pe, image_base);
+    //imports are called through their slot, call [__imp_ExitProcess] doesnt return either
+    let mut no_return = symbols::find_no_return_functions(&symbols);
+    no_return.extend(symbols::find_no_return_functions(&imports));
+    let data_symbols = data_symbols.into_iter().chain(imports).collect();
+
+    let entry = match pe.entry {
+        0 => 0,
+        entry => image_base + entry as u64,
+    };
+    //DLLs and executables linked with /DYNAMICBASE are loaded wherever ASLR puts them
+    let position_independent = pe.is_lib
+        || windows_fields.is_some_and(|fields| {
+            fields.dll_characteristics & IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE != 0
+        });
+
+    Ok(Binary {
+        format: Format::Pe,
+        bytes,
+        bitness,
+        entry,
+        segments,
+        sections,
+        symbols,
+        data_symbols,
+        fde_ranges,
+        no_return,
+        names: demangle::NameOptions::default(),
+        position_independent,
+        relocatable: false,
+        image_base,
+        bias: 0,
+    })
+}
+
+//the RUNTIME_FUNCTION entries of .pdata, x64 has one for every function that isnt a leaf
+//they are the PE version of the .eh_frame FDEs and are used the same way
+fn collect_runtime_functions(pe: &PE, image_base: u64) -> Vec<eh_frame::FdeRange> {
+    let Some(exception_data) = &pe.exception_data else {
+        return Vec::new();
+    };
+
+    let mut ranges = Vec::new();
+    for function in exception_data.functions() {
+        match function {
+            Ok(function) if function.end_address > function.begin_address => {
+                ranges.push(eh_frame::FdeRange {
+                    start: image_base + function.begin_address as u64,
+                    end: image_base + function.end_address as u64,
+                });
+            }
+            Ok(_) => {}
+            //a broken .pdata shouldnt stop the cfg generation, the other bound sources still work
+            Err(err) => {
+                log::warn!("Malformed .pdata: {}", err);
+                break;
+            }
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+//named exports, the ones in executable sections are functions and the rest data
+//forwarders point into another DLL and have no address here
+fn collect_exports(
+    pe: &PE,
+    image_base: u64,
+    segments: &[Segment],
+    fde_ranges: &[eh_frame::FdeRange],
+) -> (Vec<symbols::Symbol>, Vec<symbols::Symbol>) {
+    let mut functions = Vec::new();
+    let mut data = Vec::new();
+    let mut seen = HashSet::new();
+
+    for export in &pe.exports {
+        let Some(name) = export.name else {
+            continue;
+        };
+        if export.reexport.is_some() || !seen.insert(name) {
+            continue;
+        }
+        let address = image_base + export.rva as u64;
+
+        let executable = segments
+            .iter()
+            .any(|segment| segment.executable && segment.contains(address));
+        if executable {
+            //the export table has no sizes, .pdata does for functions that arent leaves
+            let size = fde_ranges
+                .iter()
+                .find(|range| range.start == address)
+                .map_or(0, |range| range.end - range.start);
+            functions.push(symbols::Symbol {
+                name: name.to_string(),
+                address,
+                size,
+            });
+        } else {
+            data.push(symbols::Symbol {
+                name: name.to_string(),
+                address,
+                size: 0,
+            });
+        }
+    }
+
+    (functions, data)
+}
+
+//one __imp_name symbol per import address table slot, calls to imported functions go through
+//them like call [__imp_CreateFileW]
+fn collect_imports(pe: &PE, image_base: u64) -> Vec<symbols::Symbol> {
+    let slot_size = if pe.is_64 { 8 } else { 4 };
+    pe.imports
+        .iter()
+        .map(|import| {
+            //goblin names imports by ordinal ORDINAL n, dll#n says where it comes from
+            let name = if import.name.starts_with("ORDINAL ") {
+                format!("__imp_{}#{}", import.dll, import.ordinal)
+            } else {
+                format!("__imp_{}", import.name)
+            };
+            symbols::Symbol {
+                name,
+                //offset is the RVA of the slot, rva the one of the hint/name entry
+                address: image_base + import.offset as u64,
+                size: slot_size,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+
+use goblin::elf::sym::{STT_FUNC, STT_GNU_IFUNC, STT_OBJECT};
+use goblin::elf::Elf;
+use iced_x86::{Decoder, DecoderOptions, FlowControl, Instruction, OpKind, Register};
+
+use super::demangle::{demangle, NameOptions};
+use crate::error::Error;
+
+//names of functions that never return to their caller, a call to one of these ends the path
+const NO_RETURN_FUNCTIONS: &[&str] = &[
+    "abort",
+    "exit",
+    "_exit",
+    "_Exit",
+    "quick_exit",
+    "__stack_chk_fail",
+    "__assert_fail",
+    "__cxa_throw",
+    "__cxa_rethrow",
+    "__cxa_bad_cast",
+    "__cxa_bad_typeid",
+    "_Unwind_Resume",
+    "longjmp",
+    "siglongjmp",
+    "pthread_exit",
+    "__libc_start_main",
+    "_ZSt9terminatev",
+    "ExitProcess",
+    "ExitThread",
+    "FreeLibraryAndExitThread",
+    "FatalExit",
+    "FatalAppExitA",
+    "FatalAppExitW",
+    "RtlExitUserThread",
+    "_CxxThrowException",
+    "_invalid_parameter_noinfo_noreturn",
+];
+
+//a function or data symbol with an address inside this binary
+pub struct Symbol {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+}
+
+//collect every function symbol from .symtab and .dynsym, plus one name@plt symbol per PLT stub
+//bitness is the one of the code, see Binary::bitness
+pub fn collect_symbols(elf: &Elf, buffer: &[u8], bitness: u32) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+
+    let symtab = elf
+        .syms
+        .iter()
+        .map(|sym| (sym, elf.strtab.get_at(sym.st_name)));
+    let dynsym = elf
+        .dynsyms
+        .iter()
+        .map(|sym| (sym, elf.dynstrtab.get_at(sym.st_name)));
+
+    for (sym, name) in symtab.chain(dynsym) {
+        //undefined symbols have no address in this binary
+        if sym.st_value == 0 || !(sym.st_type() == STT_FUNC || sym.st_type() == STT_GNU_IFUNC) {
+            continue;
+        }
+        if let Some(name) = name.filter(|name| !name.is_empty()) {
+            symbols.push(Symbol {
+                name: name.to_string(),
+                address: sym.st_value,
+                size: sym.st_size,
+            });
+        }
+    }
+
+    symbols.extend(collect_plt_symbols(elf, buffer, bitness));
+
+    symbols
+}
+
+//every data object from .symtab and .dynsym, plus one name@got symbol per GOT slot that
+//a relocation fills with the address of an imported symbol
+pub fn collect_data_symbols(elf: &Elf) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+
+    let symtab = elf
+        .syms
+        .iter()
+        .map(|sym| (sym, elf.strtab.get_at(sym.st_name)));
+    let dynsym = elf
+        .dynsyms
+        .iter()
+        .map(|sym| (sym, elf.dynstrtab.get_at(sym.st_name)));
+
+    for (sym, name) in symtab.chain(dynsym) {
+        if sym.st_value == 0 || sym.st_type() != STT_OBJECT {
+            continue;
+        }
+        if let Some(name) = name.filter(|name| !name.is_empty()) {
+            symbols.push(Symbol {
+                name: name.to_string(),
+                address: sym.st_value,
+                size: sym.st_size,
+            });
+        }
+    }
+
+    symbols.extend(got_slots(elf).into_iter().map(|(address, name)| Symbol {
+        name: format!("{}@got", name),
+        address,
+        size: 8,
+    }));
+
+    symbols
+}
+
+//got slot address -> imported symbol name, from the JUMP_SLOT, GLOB_DAT and similar relocations
+//x86-64 uses RELA relocations, i386 REL ones
+fn got_slots<'a>(elf: &Elf<'a>) -> HashMap<u64, &'a str> {
+    let mut got_slots = HashMap::new();
+    let relocs = elf
+        .pltrelocs
+        .iter()
+        .chain(elf.dynrelas.iter())
+        .chain(elf.dynrels.iter());
+    for reloc in relocs {
+        if let Some(name) = elf
+            .dynsyms
+            .get(reloc.r_sym)
+            .and_then(|sym| elf.dynstrtab.get_at(sym.st_name))
+            .filter(|name| !name.is_empty())
+        {
+            got_slots.insert(reloc.r_offset, name);
+        }
+    }
+    got_slots
+}
+
+//address of the got slot a PLT stub jumps through
+//x86-64 stubs jmp [rip+slot], i386 ones jmp [slot] or in PIC code jmp [ebx+offset] where ebx
+//holds the address of the GOT
+fn plt_slot(instruction: &Instruction, got_address: u64) -> Option<u64> {
+    if instruction.is_ip_rel_memory_operand() {
+        return Some(instruction.ip_rel_memory_address());
+    }
+    if instruction.memory_index() != Register::None {
+        return None;
+    }
+    let displacement = u64::from(instruction.memory_displacement32());
+    match instruction.memory_base() {
+        Register::None => Some(displacement),
+        Register::EBX => Some(got_address.wrapping_add(displacement) & 0xFFFF_FFFF),
+        _ => None,
+    }
+}
+
+//PLT stubs have no symbols, every stub jumps through a got slot though and the got slot
+//is the target of a JUMP_SLOT or GLOB_DAT relocation which names the imported function
+fn collect_plt_symbols(elf: &Elf, buffer: &[u8], bitness: u32) -> Vec<Symbol> {
+    let got_slots = got_slots(elf);
+    //the GOT that ebx points to in i386 PIC code
+    let got_address = elf
+        .section_headers
+        .iter()
+        .find(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(".got.plt"))
+        .or_else(|| {
+            elf.section_headers
+                .iter()
+                .find(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(".got"))
+        })
+        .map(|section| section.sh_addr)
+        .unwrap_or(0);
+
+    let mut symbols = Vec::new();
+
+    for section in &elf.section_headers {
+        let section_name = elf.shdr_strtab.get_at(section.sh_name).unwrap_or("");
+        if !matches!(section_name, ".plt" | ".plt.sec" | ".plt.got") {
+            continue;
+        }
+
+        let start = section.sh_offset as usize;
+        let end = start.saturating_add(section.sh_size as usize);
+        let bytes = match buffer.get(start..end) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        //every stub has the same size, the jmp can be preceded by an endbr64
+        //i386 linkers put 4 into the entry size of .plt, the stubs are 16 bytes there as well
+        let entry_size = if section.sh_entsize < 8 {
+            16
+        } else {
+            section.sh_entsize
+        };
+
+        let mut decoder = Decoder::with_ip(bitness, bytes, section.sh_addr, DecoderOptions::NONE);
+        for instruction in &mut decoder {
+            if instruction.flow_control() != FlowControl::IndirectBranch
+                || instruction.op0_kind() != OpKind::Memory
+            {
+                continue;
+            }
+            let slot = match plt_slot(&instruction, got_address) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            if let Some(name) = got_slots.get(&slot) {
+                let entry_index = (instruction.ip() - section.sh_addr) / entry_size;
+                symbols.push(Symbol {
+                    name: format!("{}@plt", name),
+                    address: section.sh_addr + entry_index * entry_size,
+                    size: entry_size,
+                });
+            }
+        }
+    }
+
+    symbols
+}
+
+//collect the addresses of known no-return functions, including their PLT stubs, the GOT slots
+//-fno-plt code calls through and the __imp_ import address table slots of a PE
+pub fn find_no_return_functions(symbols: &[Symbol]) -> HashSet<u64> {
+    symbols
+        .iter()
+        .filter(|symbol| {
+            let name = symbol.name.strip_suffix("@plt").unwrap_or(&symbol.name);
+            let name = name.strip_suffix("@got").unwrap_or(name);
+            let name = name.strip_prefix("__imp_").unwrap_or(name);
+            NO_RETURN_FUNCTIONS.contains(&name)
+        })
+        .map(|symbol| symbol.address)
+        .collect()
+}
+
+//turn the user input into a virtual address
+//input is either a hex address with 0x prefix or a (partial) function name, mangled or demangled
+pub fn resolve_address(symbols: &[Symbol], input: &str) -> Result<u64, Error> {
+    if let Some(hex) = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        return u64::from_str_radix(hex, 16).map_err(|_| Error::InvalidAddress(input.to_string()));
+    }
+
+    //every symbol can be found by its mangled and its demangled name
+    let names: Vec<[String; 2]> = symbols
+        .iter()
+        .map(|symbol| {
+            [
+                symbol.name.clone(),
+                demangle(&symbol.name, &NameOptions::default()),
+            ]
+        })
+        .collect();
+
+    //exact match first, then case insensitive, then substring, the first stage with a hit wins
+    let lowercase_input = input.to_lowercase();
+    let stages: [&dyn Fn(&str) -> bool; 3] = [
+        &|name| name == input,
+        &|name| name.to_lowercase() == lowercase_input,
+        &|name| name.to_lowercase().contains(&lowercase_input),
+    ];
+
+    for stage in stages {
+        let mut candidates: Vec<&Symbol> = symbols
+            .iter()
+            .zip(&names)
+            .filter(|(_, names)| names.iter().any(|name| stage(name)))
+            .map(|(symbol, _)| symbol)
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+
+        candidates.sort_by(|a, b| a.address.cmp(&b.address).then(a.name.cmp(&b.name)));
+        candidates.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+
+        //aliases of the same function share the address, that is not ambiguous
+        let first_address = candidates[0].address;
+        if candidates
+            .iter()
+            .all(|symbol| symbol.address == first_address)
+        {
+            return Ok(first_address);
+        }
+
+        return Err(Error::AmbiguousSymbol {
+            name: input.to_string(),
+            candidates: candidates
+                .iter()
+                .map(|symbol| (symbol.address, symbol.name.clone()))
+                .collect(),
+        });
+    }
+
+    Err(Error::SymbolNotFound(input.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, address: u64) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            address,
+            size: 0x10,
+        }
+    }
+
+    fn symbols() -> Vec<Symbol> {
+        vec![
+            symbol("main", 0x1000),
+            symbol("Main", 0x1100),
+            symbol("parse_header", 0x1200),
+            symbol("parse_body", 0x1300),
+            //alias of parse_body, in .symtab and .dynsym
+            symbol("parse_body_v2", 0x1300),
+            symbol("parse_body_v2", 0x1300),
+            symbol("_ZN6Parser5resetEv", 0x1400),
+            symbol("Setup", 0x1500),
+        ]
+    }
+
+    #[test]
+    fn resolves_addresses_and_exact_names_first() {
+        let symbols = symbols();
+        assert_eq!(resolve_address(&symbols, "0x1234").unwrap(), 0x1234);
+        assert!(matches!(
+            resolve_address(&symbols, "0xfoo"),
+            Err(Error::InvalidAddress(_))
+        ));
+        //main is also a case insensitive match of Main
+        assert_eq!(resolve_address(&symbols, "main").unwrap(), 0x1000);
+        assert_eq!(resolve_address(&symbols, "Main").unwrap(), 0x1100);
+        assert_eq!(resolve_address(&symbols, "setup").unwrap(), 0x1500);
+    }
+
+    #[test]
+    fn resolves_unique_substrings_and_demangled_names() {
+        let symbols = symbols();
+        assert_eq!(resolve_address(&symbols, "HEADER").unwrap(), 0x1200);
+        //both names of the alias are at the same address
+        assert_eq!(resolve_address(&symbols, "body").unwrap(), 0x1300);
+        assert_eq!(resolve_address(&symbols, "Parser::reset").unwrap(), 0x1400);
+        assert_eq!(
+            resolve_address(&symbols, "_ZN6Parser5resetEv").unwrap(),
+            0x1400
+        );
+    }
+
+    #[test]
+    fn reports_ambiguous_and_missing_names() {
+        let symbols = symbols();
+        match resolve_address(&symbols, "parse") {
+            Err(Error::AmbiguousSymbol { name, candidates }) => {
+                assert_eq!(name, "parse");
+                //sorted by address, the duplicate is listed once
+                assert_eq!(
+                    candidates,
+                    vec![
+                        (0x1200, "parse_header".to_string()),
+                        (0x1300, "parse_body".to_string()),
+                        (0x1300, "parse_body_v2".to_string()),
+                        (0x1400, "_ZN6Parser5resetEv".to_string()),
+                    ]
+                );
+            }
+            _ => panic!("parse should be ambiguous"),
+        }
+        assert!(matches!(
+            resolve_address(&symbols, "teardown"),
+            Err(Error::SymbolNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::callgraph::{collect_call_sites, CallGraph};
+use super::export::dot::{create_dot, DotConfig};
+use super::{write_call_graph, Binary};
+use crate::cfg::CfgBuilder;
+use crate::error::Error;
+use crate::syntax::AsmFormatter;
+
+//name of the summary file written next to the per function dot files
+const INDEX_FILE_NAME: &str = "index.txt";
+
+//address ranges [start, end) that contain code, .text if there is one, otherwise every
+//executable segment (stripped section headers, object files)
+fn code_ranges(binary: &Binary) -> Vec<(u64, u64)> {
+    //the code of an object file is often in .text.name sections next to .text
+    if let Some(text) = binary.section(".text").filter(|_| !binary.relocatable) {
+        return vec![(text.address, text.address + text.size)];
+    }
+
+    binary
+        .segments
+        .iter()
+        .filter(|segment| segment.executable)
+        .map(|segment| {
+            (
+                segment.virtual_address,
+                segment.virtual_address + segment.memory_size,
+            )
+        })
+        .collect()
+}
+
+fn is_code(code_ranges: &[(u64, u64)], address: u64) -> bool {
+    code_ranges
+        .iter()
+        .any(|(start, end)| address >= *start && address < *end)
+}
+
+//build the cfg of every function in the binary and write them into output_dir
+//functions are found through the entry point, the symbols, the .eh_frame FDEs and the direct
+//call targets found while decoding the functions found so far
+//the instructions in the dot files are printed by the formatter
+pub fn generate_all_cfgs(
+    binary: &Binary,
+    output_dir: &Path,
+    formatter: &mut AsmFormatter,
+) -> Result<(), Error> {
+    let code_ranges = code_ranges(binary);
+    let dot_config = DotConfig::default();
+
+    //function start addresses waiting to be built, sorted so the output is stable
+    let mut pending = BTreeSet::new();
+    pending.insert(binary.entry);
+    pending.extend(binary.symbols.iter().map(|symbol| symbol.address));
+    pending.extend(binary.fde_ranges.iter().map(|range| range.start));
+    pending.retain(|address| is_code(&code_ranges, *address));
+
+    fs::create_dir_all(output_dir).map_err(|err| Error::io(output_dir, err))?;
+
+    //index lines keyed by address, call targets are found out of order
+    let mut index_lines = BTreeMap::new();
+
+    //whole program call graph, it has no root
+    let mut call_graph = CallGraph::new(None, None);
+
+    //functions that were built already, call targets are only queued if they are new
+    let mut done = BTreeSet::new();
+
+    while let Some(address) = pending.pop_first() {
+        done.insert(address);
+
+        let function = match CfgBuilder::new(binary).address(address).build() {
+            Ok(function) => function,
+            Err(err) => {
+                log::warn!("function {:#X} skipped: {}", address, err);
+                continue;
+            }
+        };
+
+        //a call into the middle of a known function range, like a PLT stub in a stripped
+        //binary where only the whole .plt has an FDE, would just duplicate that function
+        if function.bounds.is_authoritative() && function.bounds.start != address {
+            continue;
+        }
+
+        //calls found in this function are functions as well
+        let call_sites = collect_call_sites(binary, &function.cfg, formatter);
+        for target in call_sites.iter().filter_map(|site| site.target) {
+            if is_code(&code_ranges, target) && !done.contains(&target) {
+                pending.insert(target);
+            }
+        }
+        call_graph.add_function(binary, address, None, call_sites);
+
+        let width = binary.address_width();
+        let file_name = format!("{:0width$X}.dot", address);
+        let dot_path = output_dir.join(&file_name);
+        fs::write(&dot_path, create_dot(&function.cfg, formatter, &dot_config))
+            .map_err(|err| Error::io(&dot_path, err))?;
+
+        let instruction_count: usize = function
+            .cfg
+            .nodes
+            .iter()
+            .map(|block| block.assembly_line.len())
+            .sum();
+
+        let mut line = String::new();
+        writeln!(
+            line,
+            "0x{:0width$X}\t0x{:0width$X}\t{:?}\t{}\t{}\t{}\t{}\t{}\t{}",
+            address,
+            function.bounds.end,
+            function.bounds.source,
+            function.cfg.nodes.len(),
+            function.cfg.edges.len(),
+            instruction_count,
+            function.escapes.len(),
+            file_name,
+            function.name
+        )
+        .unwrap();
+        index_lines.insert(address, line);
+    }
+
+    let mut index = String::new();
+    index += "# address\tend\tbounds\tblocks\tedges\tinstructions\tescapes\tfile\tname\n";
+    for line in index_lines.values() {
+        index += line;
+    }
+
+    call_graph.add_missing_targets(binary);
+    write_call_graph(&call_graph, output_dir)?;
+
+    let index_path = output_dir.join(INDEX_FILE_NAME);
+    fs::write(&index_path, index).map_err(|err| Error::io(&index_path, err))?;
+
+    log::info!(
+        "Wrote {} function cfgs to {}",
+        index_lines.len(),
+        output_dir.display()
+    );
+
+    Ok(())
+}
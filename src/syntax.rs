@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use iced_x86::{
+    FastFormatter, Formatter, GasFormatter, Instruction, IntelFormatter, MasmFormatter,
+    NasmFormatter, OpKind, Register, SymbolResolver, SymbolResult,
+};
+
+use crate::parse::Binary;
+
+//assembler syntax the instructions are printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    #[default]
+    Nasm,
+    Intel,
+    Masm,
+    //AT&T syntax
+    Gas,
+    //iced's fast formatter, masm like and a lot quicker on big binaries but less configurable
+    Fast,
+}
+
+//how instructions are printed, shared by every output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub syntax: Syntax,
+    pub uppercase_hex: bool,
+    //print branch targets and rip relative memory operands as symbol+offset instead of plain
+    //addresses
+    pub symbolize: bool,
+    //put the instruction bytes between the address and the instruction text
+    pub raw_bytes: bool,
+}
+
+impl Default for FormatOptions {
+    //uppercase hex like iced, with symbols
+    fn default() -> Self {
+        FormatOptions {
+            syntax: Syntax::Nasm,
+            uppercase_hex: true,
+            symbolize: true,
+            raw_bytes: false,
+        }
+    }
+}
+
+//prefixes the fast formatter prints in front of the mnemonic
+const PREFIXES: [&str; 8] = [
+    "lock", "rep", "repe", "repne", "xacquire", "xrelease", "bnd", "notrack",
+];
+
+//width the raw bytes column is padded to, 8 bytes cover most instructions
+const RAW_BYTES_WIDTH: usize = 8 * 3;
+
+//names addresses after the symbols of the binary, the formatter adds the offset for addresses
+//inside a symbol
+struct BinaryResolver {
+    //start address -> (name as the outputs show it, size)
+    symbols: BTreeMap<u64, (String, u64)>,
+}
+
+impl BinaryResolver {
+    fn new(binary: &Binary) -> Self {
+        let mut symbols = BTreeMap::new();
+        //functions first, an alias at the same address doesnt replace the first name
+        for symbol in binary.symbols.iter().chain(&binary.data_symbols) {
+            //a call to a PLT stub is a call to the imported function
+            let name = symbol.name.strip_suffix("@plt").unwrap_or(&symbol.name);
+            symbols
+                .entry(symbol.address)
+                .or_insert_with(|| (binary.display_name(name), symbol.size));
+        }
+        BinaryResolver { symbols }
+    }
+}
+
+impl SymbolResolver for BinaryResolver {
+    fn symbol(
+        &mut self,
+        instruction: &Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        address: u64,
+        _address_size: u32,
+    ) -> Option<SymbolResult<'_>> {
+        //immediates are rarely addresses, they are left alone
+        //i386 has no rip relative operands, globals are absolute [address] operands there
+        //fs and gs operands are thread local offsets like fs:[28h] and no addresses either
+        let resolve = match instruction.op_kind(instruction_operand?) {
+            OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => true,
+            OpKind::Memory => {
+                instruction.is_ip_rel_memory_operand()
+                    || (instruction.memory_base() == Register::None
+                        && instruction.memory_index() == Register::None
+                        && !matches!(instruction.memory_segment(), Register::FS | Register::GS))
+            }
+            _ => false,
+        };
+        if !resolve {
+            return None;
+        }
+
+        let (start, (name, size)) = self.symbols.range(..=address).next_back()?;
+        if address != *start && address - start >= *size {
+            return None;
+        }
+        Some(SymbolResult::with_str(*start, name))
+    }
+}
+
+enum Inner {
+    Full(Box<dyn Formatter>),
+    Fast(FastFormatter),
+}
+
+//formatter for the instructions of a binary, wraps the iced formatter of the chosen syntax
+pub struct AsmFormatter<'a> {
+    inner: Inner,
+    binary: &'a Binary,
+    options: FormatOptions,
+}
+
+impl<'a> AsmFormatter<'a> {
+    pub fn new(options: &FormatOptions, binary: &'a Binary) -> Self {
+        let resolver = options
+            .symbolize
+            .then(|| Box::new(BinaryResolver::new(binary)) as Box<dyn SymbolResolver>);
+
+        let inner = match options.syntax {
+            Syntax::Nasm => Inner::Full(Box::new(NasmFormatter::with_options(resolver, None))),
+            Syntax::Intel => Inner::Full(Box::new(IntelFormatter::with_options(resolver, None))),
+            Syntax::Masm => Inner::Full(Box::new(MasmFormatter::with_options(resolver, None))),
+            Syntax::Gas => Inner::Full(Box::new(GasFormatter::with_options(resolver, None))),
+            //only fails if the resolver is given to a formatter built without symbol support
+            Syntax::Fast => Inner::Fast(FastFormatter::try_with_options(resolver).unwrap()),
+        };
+
+        let mut formatter = AsmFormatter {
+            inner,
+            binary,
+            options: *options,
+        };
+        match &mut formatter.inner {
+            Inner::Full(full) => full.options_mut().set_uppercase_hex(options.uppercase_hex),
+            Inner::Fast(fast) => fast.options_mut().set_uppercase_hex(options.uppercase_hex),
+        }
+        formatter
+    }
+
+    pub fn options(&self) -> &FormatOptions {
+        &self.options
+    }
+
+    //see Binary::address_width
+    pub fn address_width(&self) -> usize {
+        self.binary.address_width()
+    }
+
+    //the whole instruction, mnemonic and operands
+    pub fn format(&mut self, instruction: &Instruction) -> String {
+        let mut text = String::new();
+        match &mut self.inner {
+            Inner::Full(full) => full.format(instruction, &mut text),
+            Inner::Fast(fast) => fast.format(instruction, &mut text),
+        }
+        text
+    }
+
+    //the mnemonic with its prefixes
+    pub fn mnemonic(&mut self, instruction: &Instruction) -> String {
+        match &mut self.inner {
+            Inner::Full(full) => {
+                let mut mnemonic = String::new();
+                full.format_mnemonic(instruction, &mut mnemonic);
+                mnemonic
+            }
+            //the fast formatter cant format parts of an instruction, so split its output
+            Inner::Fast(_) => self.split(instruction).0,
+        }
+    }
+
+    //every operand on its own
+    pub fn operands(&mut self, instruction: &Instruction) -> Vec<String> {
+        match &mut self.inner {
+            Inner::Full(full) => (0..full.operand_count(instruction))
+                .map(|operand| {
+                    let mut operand_text = String::new();
+                    //only fails for an operand index out of range
+                    full.format_operand(instruction, &mut operand_text, operand)
+                        .unwrap();
+                    operand_text
+                })
+                .collect(),
+            Inner::Fast(_) => {
+                let operands = self.split(instruction).1;
+                if operands.is_empty() {
+                    Vec::new()
+                } else {
+                    operands.split(',').map(str::to_string).collect()
+                }
+            }
+        }
+    }
+
+    //the operands as they appear after the mnemonic
+    pub fn all_operands(&mut self, instruction: &Instruction) -> String {
+        match &mut self.inner {
+            Inner::Full(full) => {
+                let mut operands = String::new();
+                full.format_all_operands(instruction, &mut operands);
+                operands
+            }
+            Inner::Fast(_) => self.split(instruction).1,
+        }
+    }
+
+    //mnemonic and operands of the fast formatter output, prefixes stay with the mnemonic
+    fn split(&mut self, instruction: &Instruction) -> (String, String) {
+        let text = self.format(instruction);
+        //operands never start with a prefix, so the mnemonic ends at the first other word
+        let mut end = 0;
+        for word in text.split(' ') {
+            end += word.len();
+            if !PREFIXES.contains(&word) {
+                break;
+            }
+            end += 1;
+        }
+        let (mnemonic, operands) = text.split_at(end.min(text.len()));
+        (mnemonic.to_string(), operands.trim_start().to_string())
+    }
+
+    //the raw bytes of the instruction at address, empty if they arent backed by the file
+    pub fn bytes(&self, address: u64, instruction: &Instruction) -> &'a [u8] {
+        self.binary
+            .bytes_at(address, instruction.len())
+            .unwrap_or_default()
+    }
+
+    //the bytes as space separated hex in the case of the options
+    pub fn hex_bytes(&self, address: u64, instruction: &Instruction) -> String {
+        let mut hex = String::new();
+        for byte in self.bytes(address, instruction) {
+            if self.options.uppercase_hex {
+                write!(hex, "{:02X} ", byte).unwrap();
+            } else {
+                write!(hex, "{:02x} ", byte).unwrap();
+            }
+        }
+        hex.truncate(hex.trim_end().len());
+        hex
+    }
+
+    //address, the raw bytes if enabled and the instruction, the line of a listing or label
+    pub fn line(&mut self, address: u64, instruction: &Instruction) -> String {
+        let mut line = if self.options.uppercase_hex {
+            format!("0x{:X} ", address)
+        } else {
+            format!("0x{:x} ", address)
+        };
+        if self.options.raw_bytes {
+            write!(
+                line,
+                "{:<width$} ",
+                self.hex_bytes(address, instruction),
+                width = RAW_BYTES_WIDTH
+            )
+            .unwrap();
+        }
+        line += &self.format(instruction);
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::symbols::Symbol;
+    use iced_x86::{Decoder, DecoderOptions};
+
+    fn symbol(name: &str, address: u64, size: u64) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            address,
+            size,
+        }
+    }
+
+    //helper at 0x1010, table at 0x1020 and the PLT stub of exit at 0x1030
+    fn binary() -> Binary {
+        let mut binary = Binary::raw(vec![0xC3; 0x40], 64, 0x1000, 0).unwrap();
+        binary.symbols.push(symbol("helper", 0x1010, 0x10));
+        binary.symbols.push(symbol("exit@plt", 0x1030, 0x10));
+        binary.data_symbols.push(symbol("table", 0x1020, 8));
+        binary
+    }
+
+    fn format(options: &FormatOptions, binary: &Binary, ip: u64, bytes: &[u8]) -> String {
+        let instruction = Decoder::with_ip(64, bytes, ip, DecoderOptions::NONE).decode();
+        AsmFormatter::new(options, binary).format(&instruction)
+    }
+
+    #[test]
+    fn names_branch_targets_and_rip_relative_operands() {
+        let binary = binary();
+        let options = FormatOptions::default();
+        // 0x1000 call 0x1010
+        // 0x1005 jmp 0x1014
+        // 0x1007 mov eax,[rip+0x17] -> 0x1024
+        // 0x100d call 0x1030
+        // 0x1012 mov rax,fs:[0x28]
+        let instructions: [(u64, &[u8]); 5] = [
+            (0x1000, &[0xE8, 0x0B, 0, 0, 0]),
+            (0x1005, &[0xEB, 0x0D]),
+            (0x1007, &[0x8B, 0x05, 0x17, 0, 0, 0]),
+            (0x100D, &[0xE8, 0x1E, 0, 0, 0]),
+            (0x1012, &[0x64, 0x48, 0x8B, 0x04, 0x25, 0x28, 0, 0, 0]),
+        ];
+        let texts: Vec<String> = instructions
+            .iter()
+            .map(|(ip, bytes)| format(&options, &binary, *ip, bytes))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "call helper",
+                "jmp short helper+4",
+                "mov eax,[rel table+4]",
+                //the PLT stub is the imported function
+                "call exit",
+                //thread local offsets arent addresses
+                "mov rax,[fs:28h]",
+            ]
+        );
+
+        let plain = FormatOptions {
+            symbolize: false,
+            ..FormatOptions::default()
+        };
+        let (ip, bytes) = instructions[1];
+        assert_eq!(
+            format(&plain, &binary, ip, bytes),
+            "jmp short 0000000000001014h"
+        );
+        let gas = FormatOptions {
+            syntax: Syntax::Gas,
+            ..FormatOptions::default()
+        };
+        let (ip, bytes) = instructions[2];
+        assert_eq!(format(&gas, &binary, ip, bytes), "mov table+4,%eax");
+    }
+}